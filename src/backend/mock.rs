@@ -0,0 +1,61 @@
+use std::sync::{Arc, Mutex};
+
+use crate::event::EventType;
+
+use super::{Callback, InputEvent, InputSink, InputSource};
+
+/// Source that replays a fixed list of events and then stops.
+#[derive(Debug, Default)]
+pub struct MockSource {
+    pub events: Vec<InputEvent>,
+}
+
+impl InputSource for MockSource {
+    fn listen(self: Box<Self>, mut callback: Callback) -> eyre::Result<()> {
+        for event in self.events {
+            callback(event);
+        }
+        Ok(())
+    }
+}
+
+/// Sink that stores every simulated event in memory.
+///
+/// The list of simulated events is shared between clones so it can be inspected after the sink
+/// has been moved into another thread.
+#[derive(Debug, Default, Clone)]
+pub struct MockSink {
+    pub simulated: Arc<Mutex<Vec<EventType>>>,
+    pub position: Option<(f64, f64)>,
+    pub display: Option<(u64, u64)>,
+}
+
+impl MockSink {
+    /// Events simulated so far.
+    pub fn events(&self) -> Vec<EventType> {
+        self.simulated.lock().unwrap().clone()
+    }
+}
+
+impl InputSink for MockSink {
+    fn simulate(&mut self, event: &EventType) -> eyre::Result<()> {
+        match *event {
+            EventType::MouseMove { x, y } => self.position = Some((x, y)),
+            EventType::MouseMoveRelative { dx, dy } => {
+                let (x, y) = self.position.unwrap_or_default();
+                self.position = Some((x + dx, y + dy));
+            }
+            _ => {}
+        }
+        self.simulated.lock().unwrap().push(*event);
+        Ok(())
+    }
+
+    fn position(&self) -> Option<(f64, f64)> {
+        self.position
+    }
+
+    fn display_size(&self) -> Option<(u64, u64)> {
+        self.display
+    }
+}
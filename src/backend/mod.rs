@@ -0,0 +1,115 @@
+use std::time::SystemTime;
+
+use clap::ValueEnum;
+
+use crate::{event::EventType, session::Device};
+
+mod mock;
+mod rdev;
+#[cfg(target_os = "linux")]
+mod uinput;
+
+pub use self::mock::{MockSink, MockSource};
+pub use self::rdev::{RdevSink, RdevSource};
+#[cfg(target_os = "linux")]
+pub use self::uinput::{input_devices, InputDevice, UinputSink, UinputSource};
+
+/// Backend used to listen to and simulate input events.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Backend {
+    /// Use the display server through rdev (X11, macOS and Windows)
+    #[default]
+    Rdev,
+    /// Read from /dev/input and inject through a uinput device (Linux only, works on Wayland and
+    /// without a display server)
+    Uinput,
+}
+
+impl Backend {
+    /// Create a source that listens to the input of this backend.
+    ///
+    /// `devices` selects the input devices to listen to, all devices are used if it is empty.
+    pub fn source(self, devices: &[String]) -> eyre::Result<Box<dyn InputSource>> {
+        match self {
+            Backend::Rdev if devices.is_empty() => Ok(Box::new(RdevSource)),
+            Backend::Rdev => Err(eyre::eyre!(
+                "Selecting input devices requires the uinput backend"
+            )),
+            #[cfg(target_os = "linux")]
+            Backend::Uinput => Ok(Box::new(UinputSource::open(devices)?)),
+            #[cfg(not(target_os = "linux"))]
+            Backend::Uinput => Err(eyre::eyre!("The uinput backend is only available on Linux")),
+        }
+    }
+
+    /// Create a sink that simulates input through this backend.
+    ///
+    /// `display` is the size of the display mouse coordinates refer to. It is only used if the
+    /// backend cannot query the display itself.
+    pub fn sink(self, display: Option<(u64, u64)>) -> eyre::Result<Box<dyn InputSink>> {
+        match self {
+            Backend::Rdev => Ok(Box::new(RdevSink::new())),
+            #[cfg(target_os = "linux")]
+            Backend::Uinput => {
+                let display = ::rdev::display_size().ok().or(display).ok_or(eyre::eyre!(
+                    "Could not get the size of the display, use `--resolution` to set it"
+                ))?;
+                Ok(Box::new(UinputSink::new(display)?))
+            }
+            #[cfg(not(target_os = "linux"))]
+            Backend::Uinput => Err(eyre::eyre!("The uinput backend is only available on Linux")),
+        }
+    }
+
+    /// Create a sink that only simulates key events.
+    ///
+    /// Unlike `sink` this does not need to know the size of the display.
+    pub fn keyboard_sink(self) -> eyre::Result<Box<dyn InputSink>> {
+        match self {
+            Backend::Rdev => Ok(Box::new(RdevSink::new())),
+            #[cfg(target_os = "linux")]
+            Backend::Uinput => Ok(Box::new(UinputSink::keyboard()?)),
+            #[cfg(not(target_os = "linux"))]
+            Backend::Uinput => Err(eyre::eyre!("The uinput backend is only available on Linux")),
+        }
+    }
+}
+
+/// Input event received from an `InputSource`.
+#[derive(Debug, Clone, Copy)]
+pub struct InputEvent {
+    pub time: SystemTime,
+    pub event: EventType,
+    /// Id of the device the event was received from, see `InputSource::devices`
+    pub device: Option<u16>,
+}
+
+/// Callback invoked for every event received by an `InputSource`.
+pub type Callback = Box<dyn FnMut(InputEvent) + Send>;
+
+/// Source of keyboard and mouse events.
+pub trait InputSource: Send {
+    /// Call `callback` for every input event.
+    ///
+    /// This blocks until the source is exhausted, which for a real device might never happen.
+    fn listen(self: Box<Self>, callback: Callback) -> eyre::Result<()>;
+
+    /// Devices events are received from if the source can tell them apart.
+    fn devices(&self) -> Vec<Device> {
+        Vec::new()
+    }
+}
+
+/// Target that keyboard and mouse events are simulated on.
+pub trait InputSink: Send {
+    /// Simulate a single event.
+    ///
+    /// Relative mouse motion is applied to the current position of the pointer.
+    fn simulate(&mut self, event: &EventType) -> eyre::Result<()>;
+
+    /// Current position of the mouse pointer if it is known.
+    fn position(&self) -> Option<(f64, f64)>;
+
+    /// Resolution of the display if it is known.
+    fn display_size(&self) -> Option<(u64, u64)>;
+}
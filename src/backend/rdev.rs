@@ -0,0 +1,77 @@
+use rdev::{listen, simulate};
+
+use crate::{event::EventType, mouse::Pointer};
+
+use super::{Callback, InputEvent, InputSink, InputSource};
+
+/// Listens to input events through rdev.
+#[derive(Debug, Default)]
+pub struct RdevSource;
+
+impl InputSource for RdevSource {
+    fn listen(self: Box<Self>, mut callback: Callback) -> eyre::Result<()> {
+        listen(move |event| {
+            callback(InputEvent {
+                time: event.time,
+                event: event.event_type.into(),
+                device: None,
+            })
+        })
+        .map_err(|e| eyre::eyre!("Could not listen: {:?}", e))
+    }
+}
+
+/// Simulates input events through rdev.
+pub struct RdevSink {
+    pointer: Option<Pointer>,
+    last_position: Option<(f64, f64)>,
+}
+
+impl RdevSink {
+    pub fn new() -> Self {
+        Self {
+            pointer: Pointer::open(),
+            last_position: None,
+        }
+    }
+}
+
+impl Default for RdevSink {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl InputSink for RdevSink {
+    fn simulate(&mut self, event: &EventType) -> eyre::Result<()> {
+        // Relative motion is resolved against the current pointer position right before it is
+        // simulated. If the pointer cannot be queried the last simulated position is used instead.
+        let simulated = match *event {
+            EventType::MouseMoveRelative { dx, dy } => {
+                let (x, y) = self.position().unwrap_or_default();
+                rdev::EventType::MouseMove {
+                    x: x + dx,
+                    y: y + dy,
+                }
+            }
+            e => e.to_rdev().expect("absolute event can be simulated"),
+        };
+
+        if let rdev::EventType::MouseMove { x, y } = simulated {
+            self.last_position = Some((x, y));
+        }
+
+        simulate(&simulated).map_err(|_| eyre::eyre!("Could not simulate {}", event))
+    }
+
+    fn position(&self) -> Option<(f64, f64)> {
+        self.pointer
+            .as_ref()
+            .and_then(|p| p.position())
+            .or(self.last_position)
+    }
+
+    fn display_size(&self) -> Option<(u64, u64)> {
+        rdev::display_size().ok()
+    }
+}
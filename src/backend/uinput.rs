@@ -0,0 +1,520 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    thread,
+};
+
+use evdev::{
+    uinput::{VirtualDevice, VirtualDeviceBuilder},
+    AbsInfo, AbsoluteAxisType, AttributeSet, InputEventKind, RelativeAxisType, Synchronization,
+    UinputAbsSetup,
+};
+
+use crate::{event::EventType, keys::Key, mouse::MouseButton, session::Device};
+
+use super::{Callback, InputEvent, InputSink, InputSource};
+
+/// Kernel key codes of every key known to mkrp.
+const KEYS: &[(Key, evdev::Key)] = &[
+    (Key::A, evdev::Key::KEY_A),
+    (Key::B, evdev::Key::KEY_B),
+    (Key::C, evdev::Key::KEY_C),
+    (Key::D, evdev::Key::KEY_D),
+    (Key::E, evdev::Key::KEY_E),
+    (Key::F, evdev::Key::KEY_F),
+    (Key::G, evdev::Key::KEY_G),
+    (Key::H, evdev::Key::KEY_H),
+    (Key::I, evdev::Key::KEY_I),
+    (Key::J, evdev::Key::KEY_J),
+    (Key::K, evdev::Key::KEY_K),
+    (Key::L, evdev::Key::KEY_L),
+    (Key::M, evdev::Key::KEY_M),
+    (Key::N, evdev::Key::KEY_N),
+    (Key::O, evdev::Key::KEY_O),
+    (Key::P, evdev::Key::KEY_P),
+    (Key::Q, evdev::Key::KEY_Q),
+    (Key::R, evdev::Key::KEY_R),
+    (Key::S, evdev::Key::KEY_S),
+    (Key::T, evdev::Key::KEY_T),
+    (Key::U, evdev::Key::KEY_U),
+    (Key::V, evdev::Key::KEY_V),
+    (Key::W, evdev::Key::KEY_W),
+    (Key::X, evdev::Key::KEY_X),
+    (Key::Y, evdev::Key::KEY_Y),
+    (Key::Z, evdev::Key::KEY_Z),
+    (Key::Num0, evdev::Key::KEY_0),
+    (Key::Num1, evdev::Key::KEY_1),
+    (Key::Num2, evdev::Key::KEY_2),
+    (Key::Num3, evdev::Key::KEY_3),
+    (Key::Num4, evdev::Key::KEY_4),
+    (Key::Num5, evdev::Key::KEY_5),
+    (Key::Num6, evdev::Key::KEY_6),
+    (Key::Num7, evdev::Key::KEY_7),
+    (Key::Num8, evdev::Key::KEY_8),
+    (Key::Num9, evdev::Key::KEY_9),
+    (Key::F1, evdev::Key::KEY_F1),
+    (Key::F2, evdev::Key::KEY_F2),
+    (Key::F3, evdev::Key::KEY_F3),
+    (Key::F4, evdev::Key::KEY_F4),
+    (Key::F5, evdev::Key::KEY_F5),
+    (Key::F6, evdev::Key::KEY_F6),
+    (Key::F7, evdev::Key::KEY_F7),
+    (Key::F8, evdev::Key::KEY_F8),
+    (Key::F9, evdev::Key::KEY_F9),
+    (Key::F10, evdev::Key::KEY_F10),
+    (Key::F11, evdev::Key::KEY_F11),
+    (Key::F12, evdev::Key::KEY_F12),
+    (Key::SemiColon, evdev::Key::KEY_SEMICOLON),
+    (Key::Comma, evdev::Key::KEY_COMMA),
+    (Key::Dot, evdev::Key::KEY_DOT),
+    (Key::Slash, evdev::Key::KEY_SLASH),
+    (Key::Backslash, evdev::Key::KEY_BACKSLASH),
+    (Key::LBracket, evdev::Key::KEY_LEFTBRACE),
+    (Key::RBracket, evdev::Key::KEY_RIGHTBRACE),
+    (Key::Quote, evdev::Key::KEY_APOSTROPHE),
+    (Key::Backquote, evdev::Key::KEY_GRAVE),
+    (Key::Minus, evdev::Key::KEY_MINUS),
+    (Key::Equal, evdev::Key::KEY_EQUAL),
+    (Key::LCtrl, evdev::Key::KEY_LEFTCTRL),
+    (Key::RCtrl, evdev::Key::KEY_RIGHTCTRL),
+    (Key::LShift, evdev::Key::KEY_LEFTSHIFT),
+    (Key::RShift, evdev::Key::KEY_RIGHTSHIFT),
+    (Key::LAlt, evdev::Key::KEY_LEFTALT),
+    (Key::RAlt, evdev::Key::KEY_RIGHTALT),
+    (Key::LSuper, evdev::Key::KEY_LEFTMETA),
+    (Key::RSuper, evdev::Key::KEY_RIGHTMETA),
+    (Key::Backspace, evdev::Key::KEY_BACKSPACE),
+    (Key::Delete, evdev::Key::KEY_DELETE),
+    (Key::Space, evdev::Key::KEY_SPACE),
+    (Key::Return, evdev::Key::KEY_ENTER),
+    (Key::Escape, evdev::Key::KEY_ESC),
+    (Key::Tab, evdev::Key::KEY_TAB),
+    (Key::Capslock, evdev::Key::KEY_CAPSLOCK),
+    (Key::Up, evdev::Key::KEY_UP),
+    (Key::Down, evdev::Key::KEY_DOWN),
+    (Key::Left, evdev::Key::KEY_LEFT),
+    (Key::Right, evdev::Key::KEY_RIGHT),
+    (Key::Home, evdev::Key::KEY_HOME),
+    (Key::End, evdev::Key::KEY_END),
+    (Key::Pageup, evdev::Key::KEY_PAGEUP),
+    (Key::Pagedown, evdev::Key::KEY_PAGEDOWN),
+    (Key::Insert, evdev::Key::KEY_INSERT),
+    (Key::PrintScreen, evdev::Key::KEY_SYSRQ),
+    (Key::IntlBackslash, evdev::Key::KEY_102ND),
+];
+
+/// Kernel button codes of every mouse button known to mkrp.
+const BUTTONS: &[(MouseButton, evdev::Key)] = &[
+    (MouseButton::Left, evdev::Key::BTN_LEFT),
+    (MouseButton::Right, evdev::Key::BTN_RIGHT),
+    (MouseButton::Middle, evdev::Key::BTN_MIDDLE),
+];
+
+fn to_kernel_key(key: Key) -> Option<evdev::Key> {
+    KEYS.iter().find(|(k, _)| *k == key).map(|(_, code)| *code)
+}
+
+fn to_kernel_button(button: MouseButton) -> Option<evdev::Key> {
+    BUTTONS
+        .iter()
+        .find(|(b, _)| *b == button)
+        .map(|(_, code)| *code)
+}
+
+/// Map a key event from the kernel to a key or button press or release.
+///
+/// Auto repeat events and codes without a counterpart are ignored.
+fn from_kernel_key(code: evdev::Key, value: i32) -> Option<EventType> {
+    let pressed = match value {
+        0 => false,
+        1 => true,
+        _ => return None,
+    };
+
+    if let Some((key, _)) = KEYS.iter().find(|(_, c)| *c == code) {
+        return Some(match pressed {
+            true => EventType::KeyPress(*key),
+            false => EventType::KeyRelease(*key),
+        });
+    }
+
+    BUTTONS
+        .iter()
+        .find(|(_, c)| *c == code)
+        .map(|(button, _)| match pressed {
+            true => EventType::ButtonPress(*button),
+            false => EventType::ButtonRelease(*button),
+        })
+}
+
+/// Keyboard or mouse found in `/dev/input`.
+pub struct InputDevice {
+    pub path: PathBuf,
+    pub name: String,
+    device: evdev::Device,
+}
+
+impl InputDevice {
+    /// Check if the device is selected by `selection`, which is either the path of the device,
+    /// the name of its event node like `event3` or the name of the device.
+    fn matches(&self, selection: &str) -> bool {
+        self.path == Path::new(selection)
+            || self.path.file_name().is_some_and(|n| n == selection)
+            || self.name == selection
+    }
+}
+
+/// List every keyboard and mouse that can be read, ordered by path.
+///
+/// Reading the devices requires read access to `/dev/input/event*`, usually through membership of
+/// the `input` group. Devices that cannot be opened are left out.
+pub fn input_devices() -> Vec<InputDevice> {
+    let mut devices: Vec<InputDevice> = evdev::enumerate()
+        .filter(|(_, device)| {
+            let keys = device.supported_keys().is_some_and(|keys| {
+                keys.contains(evdev::Key::KEY_A) || keys.contains(evdev::Key::BTN_LEFT)
+            });
+            let motion = device
+                .supported_relative_axes()
+                .is_some_and(|axes| axes.contains(RelativeAxisType::REL_X));
+            keys || motion
+        })
+        .map(|(path, device)| InputDevice {
+            path,
+            name: device.name().unwrap_or("unknown").to_string(),
+            device,
+        })
+        .collect();
+    devices.sort_by(|a, b| a.path.cmp(&b.path));
+    devices
+}
+
+/// Reads input events from keyboards and mice in `/dev/input`.
+///
+/// The kernel only reports relative mouse motion, so every mouse move is received as
+/// `MouseMoveRelative`. Every event carries the index of the device it was read from.
+pub struct UinputSource {
+    devices: Vec<InputDevice>,
+}
+
+impl UinputSource {
+    /// Open the devices matching any of `selection`, or every keyboard and mouse if the selection
+    /// is empty.
+    pub fn open(selection: &[String]) -> eyre::Result<Self> {
+        let mut devices = input_devices();
+        if devices.is_empty() {
+            return Err(eyre::eyre!(
+                "Could not find any keyboard or mouse in /dev/input. Check that the user is allowed to read the devices"
+            ));
+        }
+
+        if !selection.is_empty() {
+            if let Some(unknown) = selection
+                .iter()
+                .find(|s| !devices.iter().any(|d| d.matches(s)))
+            {
+                return Err(eyre::eyre!("Unknown input device '{}'", unknown));
+            }
+            devices.retain(|d| selection.iter().any(|s| d.matches(s)));
+        }
+
+        Ok(Self { devices })
+    }
+}
+
+impl InputSource for UinputSource {
+    fn listen(self: Box<Self>, callback: Callback) -> eyre::Result<()> {
+        let callback = Arc::new(Mutex::new(callback));
+        let handles: Vec<_> = self
+            .devices
+            .into_iter()
+            .enumerate()
+            .map(|(id, device)| {
+                let callback = callback.clone();
+                thread::spawn(move || read_device(device.device, id as u16, callback))
+            })
+            .collect();
+
+        for handle in handles {
+            handle
+                .join()
+                .map_err(|_| eyre::eyre!("Input device thread panicked"))??;
+        }
+        Ok(())
+    }
+
+    fn devices(&self) -> Vec<Device> {
+        self.devices
+            .iter()
+            .enumerate()
+            .map(|(id, device)| Device {
+                id: id as u16,
+                name: device.name.clone(),
+            })
+            .collect()
+    }
+}
+
+/// Forward events of a single device until it can no longer be read.
+fn read_device(
+    mut device: evdev::Device,
+    id: u16,
+    callback: Arc<Mutex<Callback>>,
+) -> eyre::Result<()> {
+    // Motion along both axes is reported as separate events followed by a sync event. They are
+    // combined into a single move.
+    let (mut dx, mut dy) = (0, 0);
+    loop {
+        for event in device.fetch_events()? {
+            let received = match event.kind() {
+                InputEventKind::Key(code) => from_kernel_key(code, event.value()),
+                InputEventKind::RelAxis(RelativeAxisType::REL_X) => {
+                    dx += event.value();
+                    None
+                }
+                InputEventKind::RelAxis(RelativeAxisType::REL_Y) => {
+                    dy += event.value();
+                    None
+                }
+                InputEventKind::RelAxis(RelativeAxisType::REL_WHEEL) => Some(EventType::Wheel {
+                    delta_x: 0,
+                    delta_y: event.value() as i64,
+                }),
+                InputEventKind::RelAxis(RelativeAxisType::REL_HWHEEL) => Some(EventType::Wheel {
+                    delta_x: event.value() as i64,
+                    delta_y: 0,
+                }),
+                InputEventKind::Synchronization(Synchronization::SYN_REPORT)
+                    if dx != 0 || dy != 0 =>
+                {
+                    let motion = EventType::MouseMoveRelative {
+                        dx: dx as f64,
+                        dy: dy as f64,
+                    };
+                    (dx, dy) = (0, 0);
+                    Some(motion)
+                }
+                _ => None,
+            };
+
+            if let Some(received) = received {
+                let mut callback = callback.lock().expect("callback lock poisoned");
+                callback(InputEvent {
+                    time: event.timestamp(),
+                    event: received,
+                    device: Some(id),
+                });
+            }
+        }
+    }
+}
+
+/// Create the virtual keyboard and the virtual pointer spanning the display.
+fn create_keyboard() -> std::io::Result<VirtualDevice> {
+    let mut keys = AttributeSet::<evdev::Key>::new();
+    for (_, code) in KEYS {
+        keys.insert(*code);
+    }
+    VirtualDeviceBuilder::new()?
+        .name("mkrp virtual keyboard")
+        .with_keys(&keys)?
+        .build()
+}
+
+fn create_pointer(display: (u64, u64)) -> std::io::Result<VirtualDevice> {
+    let mut buttons = AttributeSet::<evdev::Key>::new();
+    for (_, code) in BUTTONS {
+        buttons.insert(*code);
+    }
+    let mut relative = AttributeSet::<RelativeAxisType>::new();
+    relative.insert(RelativeAxisType::REL_X);
+    relative.insert(RelativeAxisType::REL_Y);
+    relative.insert(RelativeAxisType::REL_WHEEL);
+    relative.insert(RelativeAxisType::REL_HWHEEL);
+    let axis =
+        |code, size: u64| UinputAbsSetup::new(code, AbsInfo::new(0, 0, size as i32 - 1, 0, 0, 0));
+    VirtualDeviceBuilder::new()?
+        .name("mkrp virtual pointer")
+        .with_keys(&buttons)?
+        .with_absolute_axis(&axis(AbsoluteAxisType::ABS_X, display.0))?
+        .with_absolute_axis(&axis(AbsoluteAxisType::ABS_Y, display.1))?
+        .with_relative_axes(&relative)?
+        .build()
+}
+
+fn uinput_error(e: std::io::Error) -> eyre::Report {
+    eyre::eyre!(
+        "Could not create a uinput device, check that /dev/uinput is writable: {}",
+        e
+    )
+}
+
+/// Simulates input events through virtual uinput devices.
+///
+/// Keys are sent through a virtual keyboard and mouse events through a virtual pointer with an
+/// absolute axis that spans the given display, so absolute coordinates are placed exactly without
+/// being affected by pointer acceleration. Relative motion is sent as relative events and moves
+/// the pointer from wherever it currently is. A sink created with `keyboard` has no pointer and
+/// only simulates keys. Creating the devices requires write access to `/dev/uinput`.
+pub struct UinputSink {
+    keyboard: VirtualDevice,
+    /// Virtual pointer and the size of the display it spans, `None` for a keyboard only sink
+    pointer: Option<(VirtualDevice, (u64, u64))>,
+    position: Option<(f64, f64)>,
+}
+
+impl UinputSink {
+    pub fn new(display: (u64, u64)) -> eyre::Result<Self> {
+        let keyboard = create_keyboard().map_err(uinput_error)?;
+        let pointer = create_pointer(display).map_err(uinput_error)?;
+        Ok(Self {
+            keyboard,
+            pointer: Some((pointer, display)),
+            position: None,
+        })
+    }
+
+    /// Create a sink with only a virtual keyboard, which does not need the size of the display.
+    ///
+    /// Mouse events cannot be simulated on this sink.
+    pub fn keyboard() -> eyre::Result<Self> {
+        Ok(Self {
+            keyboard: create_keyboard().map_err(uinput_error)?,
+            pointer: None,
+            position: None,
+        })
+    }
+
+    fn pointer(&mut self) -> eyre::Result<(&mut VirtualDevice, (u64, u64))> {
+        self.pointer
+            .as_mut()
+            .map(|(pointer, display)| (pointer, *display))
+            .ok_or_else(|| eyre::eyre!("Mouse events require a virtual pointer"))
+    }
+
+    fn move_to(&mut self, x: f64, y: f64) -> eyre::Result<()> {
+        let (pointer, display) = self.pointer()?;
+        let max_x = display.0.saturating_sub(1) as f64;
+        let max_y = display.1.saturating_sub(1) as f64;
+        pointer.emit(&[
+            evdev::InputEvent::new(
+                evdev::EventType::ABSOLUTE,
+                AbsoluteAxisType::ABS_X.0,
+                x.clamp(0.0, max_x) as i32,
+            ),
+            evdev::InputEvent::new(
+                evdev::EventType::ABSOLUTE,
+                AbsoluteAxisType::ABS_Y.0,
+                y.clamp(0.0, max_y) as i32,
+            ),
+        ])?;
+        self.position = Some((x, y));
+        Ok(())
+    }
+
+    /// Move the pointer relative to its current position.
+    ///
+    /// The position of the pointer cannot be queried through uinput, so the tracked position is
+    /// only known if an absolute move was simulated before.
+    fn move_by(&mut self, dx: f64, dy: f64) -> eyre::Result<()> {
+        self.pointer()?.0.emit(&[
+            evdev::InputEvent::new(
+                evdev::EventType::RELATIVE,
+                RelativeAxisType::REL_X.0,
+                dx.round() as i32,
+            ),
+            evdev::InputEvent::new(
+                evdev::EventType::RELATIVE,
+                RelativeAxisType::REL_Y.0,
+                dy.round() as i32,
+            ),
+        ])?;
+        self.position = self.position.map(|(x, y)| (x + dx, y + dy));
+        Ok(())
+    }
+}
+
+impl InputSink for UinputSink {
+    fn simulate(&mut self, event: &EventType) -> eyre::Result<()> {
+        let unsupported = || eyre::eyre!("Could not simulate {}", event);
+        let key = |code: evdev::Key, value| {
+            evdev::InputEvent::new(evdev::EventType::KEY, code.code(), value)
+        };
+
+        match *event {
+            EventType::KeyPress(k) => {
+                let code = to_kernel_key(k).ok_or_else(unsupported)?;
+                self.keyboard.emit(&[key(code, 1)])?;
+            }
+            EventType::KeyRelease(k) => {
+                let code = to_kernel_key(k).ok_or_else(unsupported)?;
+                self.keyboard.emit(&[key(code, 0)])?;
+            }
+            EventType::ButtonPress(b) => {
+                let code = to_kernel_button(b).ok_or_else(unsupported)?;
+                self.pointer()?.0.emit(&[key(code, 1)])?;
+            }
+            EventType::ButtonRelease(b) => {
+                let code = to_kernel_button(b).ok_or_else(unsupported)?;
+                self.pointer()?.0.emit(&[key(code, 0)])?;
+            }
+            EventType::MouseMove { x, y } => self.move_to(x, y)?,
+            EventType::MouseMoveRelative { dx, dy } => self.move_by(dx, dy)?,
+            EventType::Wheel { delta_x, delta_y } => {
+                let mut events = Vec::new();
+                if delta_x != 0 {
+                    events.push(evdev::InputEvent::new(
+                        evdev::EventType::RELATIVE,
+                        RelativeAxisType::REL_HWHEEL.0,
+                        delta_x as i32,
+                    ));
+                }
+                if delta_y != 0 {
+                    events.push(evdev::InputEvent::new(
+                        evdev::EventType::RELATIVE,
+                        RelativeAxisType::REL_WHEEL.0,
+                        delta_y as i32,
+                    ));
+                }
+                self.pointer()?.0.emit(&events)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn position(&self) -> Option<(f64, f64)> {
+        self.position
+    }
+
+    fn display_size(&self) -> Option<(u64, u64)> {
+        self.pointer.as_ref().map(|(_, display)| *display)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn map_kernel_codes() {
+        for value in 1..=Key::IntlBackslash as u8 {
+            let key = Key::from(value);
+            if key == Key::Unknown {
+                continue;
+            }
+            let code = to_kernel_key(key).expect("every key has a kernel code");
+            assert_eq!(from_kernel_key(code, 1), Some(EventType::KeyPress(key)));
+            assert_eq!(from_kernel_key(code, 0), Some(EventType::KeyRelease(key)));
+        }
+
+        let code = to_kernel_button(MouseButton::Right).unwrap();
+        assert_eq!(
+            from_kernel_key(code, 1),
+            Some(EventType::ButtonPress(MouseButton::Right))
+        );
+
+        // Auto repeat
+        assert_eq!(from_kernel_key(evdev::Key::KEY_A, 2), None);
+        assert_eq!(from_kernel_key(evdev::Key::KEY_VOLUMEUP, 1), None);
+    }
+}
@@ -0,0 +1,278 @@
+use std::time::Duration;
+
+use crate::{
+    event::{Event, EventType},
+    keys::{Key, KeyState},
+    layout::{Layout, Stroke},
+    mouse::{MouseButton, MouseState},
+    session::Session,
+};
+
+/// Builds a session from code.
+///
+/// Every event is played right after the previous one unless a `wait` is put in between.
+///
+/// ```
+/// use mkrp::{mouse::MouseButton, Key, SessionBuilder};
+///
+/// let session = SessionBuilder::new()
+///     .press(Key::LCtrl)
+///     .tap(Key::C)
+///     .release(Key::LCtrl)
+///     .wait(100)
+///     .click_at(MouseButton::Left, 200.0, 300.0)
+///     .type_text("Hello!")
+///     .build()?;
+/// assert_eq!(session.total_time.as_millis(), 100);
+/// # Ok::<(), eyre::Report>(())
+/// ```
+#[derive(Debug, Default)]
+pub struct SessionBuilder {
+    events: Vec<Event>,
+    delay: Duration,
+    keys: KeyState,
+    buttons: MouseState,
+    display: Option<(u64, u64)>,
+    layout: Layout,
+    /// Time every key is held down when typing text
+    hold: Duration,
+    /// First error found while building, reported by `build`
+    error: Option<String>,
+}
+
+impl SessionBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Resolution of the display the mouse coordinates refer to.
+    pub fn display(mut self, width: u64, height: u64) -> Self {
+        self.display = Some((width, height));
+        self
+    }
+
+    /// Keyboard layout used to type text, US by default.
+    pub fn layout(mut self, layout: Layout) -> Self {
+        self.layout = layout;
+        self
+    }
+
+    /// Hold every key down for the given number of milliseconds when typing text.
+    pub fn hold(mut self, ms: u64) -> Self {
+        self.hold = Duration::from_millis(ms);
+        self
+    }
+
+    /// Wait the given number of milliseconds before the next event.
+    ///
+    /// A session cannot end with a wait, so `build` fails if no event follows.
+    pub fn wait(mut self, ms: u64) -> Self {
+        self.delay += Duration::from_millis(ms);
+        self
+    }
+
+    pub fn press(mut self, key: Key) -> Self {
+        if self.keys.is_pressed(key) {
+            self.fail(format!(
+                "key {} is pressed while already held",
+                key.to_str()
+            ));
+        }
+        self.keys.set_pressed(key);
+        self.push(EventType::KeyPress(key))
+    }
+
+    pub fn release(mut self, key: Key) -> Self {
+        if !self.keys.is_pressed(key) {
+            self.fail(format!(
+                "key {} is released without being pressed",
+                key.to_str()
+            ));
+        }
+        self.keys.set_released(key);
+        self.push(EventType::KeyRelease(key))
+    }
+
+    /// Press and release a key.
+    pub fn tap(self, key: Key) -> Self {
+        self.press(key).release(key)
+    }
+
+    pub fn move_to(self, x: f64, y: f64) -> Self {
+        self.push(EventType::MouseMove { x, y })
+    }
+
+    pub fn move_by(self, dx: f64, dy: f64) -> Self {
+        self.push(EventType::MouseMoveRelative { dx, dy })
+    }
+
+    pub fn press_button(mut self, button: MouseButton) -> Self {
+        if self.buttons.is_pressed(button) {
+            self.fail(format!(
+                "button {} is pressed while already held",
+                button.to_str()
+            ));
+        }
+        self.buttons.set_pressed(button);
+        self.push(EventType::ButtonPress(button))
+    }
+
+    pub fn release_button(mut self, button: MouseButton) -> Self {
+        if !self.buttons.is_pressed(button) {
+            self.fail(format!(
+                "button {} is released without being pressed",
+                button.to_str()
+            ));
+        }
+        self.buttons.set_released(button);
+        self.push(EventType::ButtonRelease(button))
+    }
+
+    /// Press and release a mouse button at the current position.
+    pub fn click(self, button: MouseButton) -> Self {
+        self.press_button(button).release_button(button)
+    }
+
+    /// Move the mouse to the given position and click there.
+    pub fn click_at(self, button: MouseButton, x: f64, y: f64) -> Self {
+        self.move_to(x, y).click(button)
+    }
+
+    pub fn scroll(self, delta_x: i64, delta_y: i64) -> Self {
+        self.push(EventType::Wheel { delta_x, delta_y })
+    }
+
+    /// Type the text on the keyboard layout, holding shift and AltGr where needed.
+    pub fn type_text(mut self, text: &str) -> Self {
+        for c in text.chars() {
+            self = self.type_char(c);
+        }
+        self
+    }
+
+    /// Type a single character on the keyboard layout.
+    pub fn type_char(mut self, c: char) -> Self {
+        match self.layout.strokes(c) {
+            Some(strokes) => strokes.into_iter().fold(self, Self::stroke),
+            None => {
+                self.fail(format!(
+                    "character {:?} cannot be typed on the {} layout",
+                    c, self.layout
+                ));
+                self
+            }
+        }
+    }
+
+    fn stroke(mut self, stroke: Stroke) -> Self {
+        if stroke.shift {
+            self = self.press(Key::LShift);
+        }
+        if stroke.altgr {
+            self = self.press(Key::RAlt);
+        }
+        self = self.press(stroke.key);
+        self.delay += self.hold;
+        self = self.release(stroke.key);
+        if stroke.altgr {
+            self = self.release(Key::RAlt);
+        }
+        if stroke.shift {
+            self = self.release(Key::LShift);
+        }
+        self
+    }
+
+    /// Create the session.
+    ///
+    /// Fails if a key or button is pressed twice, released without being pressed or still held
+    /// at the end, or if the last `wait` is not followed by an event.
+    pub fn build(self) -> eyre::Result<Session> {
+        if let Some(error) = self.error {
+            return Err(eyre::eyre!(error));
+        }
+        if !self.delay.is_zero() {
+            return Err(eyre::eyre!(
+                "wait of {}ms at the end of the session is not followed by an event",
+                self.delay.as_millis()
+            ));
+        }
+
+        let held: Vec<_> = self
+            .keys
+            .iter()
+            .map(Key::to_str)
+            .chain(self.buttons.iter().map(MouseButton::to_str))
+            .collect();
+        if !held.is_empty() {
+            return Err(eyre::eyre!(
+                "still held at the end of the session: {}",
+                held.join(", ")
+            ));
+        }
+
+        Ok(Session::new(self.events, self.display))
+    }
+
+    fn push(mut self, event: EventType) -> Self {
+        self.events.push(Event {
+            delay: std::mem::take(&mut self.delay),
+            event,
+            device: None,
+        });
+        self
+    }
+
+    fn fail(&mut self, error: String) {
+        self.error.get_or_insert(error);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn build_session() {
+        let session = SessionBuilder::new()
+            .press(Key::LCtrl)
+            .tap(Key::C)
+            .release(Key::LCtrl)
+            .wait(250)
+            .click_at(MouseButton::Left, 10.0, 20.0)
+            .wait(50)
+            .type_text("aB")
+            .build()
+            .unwrap();
+
+        assert_eq!(session.total_time, Duration::from_millis(300));
+        assert_eq!(
+            session.to_string(),
+            "0,kp,60\n0,kp,3\n0,kr,3\n0,kr,60\n250,mm,10,20\n0,mp,1\n0,mr,1\n50,kp,1\n0,kr,1\n\
+             0,kp,62\n0,kp,2\n0,kr,2\n0,kr,62\n"
+        );
+    }
+
+    #[test]
+    fn check_balance() {
+        assert!(SessionBuilder::new().press(Key::A).build().is_err());
+        assert!(SessionBuilder::new().release(Key::A).build().is_err());
+        assert!(SessionBuilder::new()
+            .press(Key::A)
+            .press(Key::A)
+            .release(Key::A)
+            .build()
+            .is_err());
+        assert!(SessionBuilder::new()
+            .press_button(MouseButton::Right)
+            .build()
+            .is_err());
+        assert!(SessionBuilder::new().type_text("ü").build().is_err());
+        assert!(SessionBuilder::new().tap(Key::A).wait(100).build().is_err());
+        assert!(SessionBuilder::new()
+            .layout(Layout::De)
+            .type_text("ü")
+            .build()
+            .is_ok());
+    }
+}
@@ -1,7 +1,15 @@
-use std::path::PathBuf;
+use std::{path::PathBuf, time::Duration};
 
 use clap::{crate_description, crate_version, Args, Parser, Subcommand};
 
+use mkrp::{
+    backend::Backend,
+    humanize::{Distribution, Jitter},
+    layout::Layout,
+    schedule::parse_duration,
+    session::Position,
+};
+
 pub trait Run {
     fn run(self) -> eyre::Result<()>;
 }
@@ -21,6 +29,16 @@ pub struct Cli {
 pub enum Cmd {
     Record(Record),
     Play(Play),
+    Debug(Debug),
+    Devices(Devices),
+    Type(Type),
+    Text(Text),
+    Inspect(Inspect),
+    Check(Check),
+    Trim(Trim),
+    Cut(Cut),
+    Extract(Extract),
+    Concat(Concat),
 }
 
 /// Record mouse and keyboard events and save them into a file
@@ -44,6 +62,57 @@ pub struct Record {
     #[arg(short, long)]
     pub stop_key: Option<String>,
 
+    /// Stop recording after the given amount of time has passed
+    ///
+    /// The duration is a sequence of numbers with units such as `1h`, `5m`, `1m30s` or `500ms`.
+    /// A number without a unit is treated as seconds.
+    #[arg(long, value_name = "DURATION", value_parser = parse_duration)]
+    pub max_duration: Option<Duration>,
+
+    /// Stop recording after the given number of events have been recorded
+    #[arg(long, value_name = "COUNT")]
+    pub max_events: Option<usize>,
+
+    /// Stop recording when no input has been received for the given duration
+    ///
+    /// The idle timer starts after the first recorded event. The idle time itself is not part of
+    /// the recording.
+    #[arg(long, value_name = "DURATION", value_parser = parse_duration)]
+    pub idle_timeout: Option<Duration>,
+
+    /// Key used to place a marker at the current position of the recording
+    ///
+    /// Markers are named `m1`, `m2`, ... in the order they are placed and can be used to refer to
+    /// a position in the session, for example with `play --from`. The marker key itself is not
+    /// recorded.
+    #[arg(short, long, value_name = "KEY")]
+    pub marker_key: Option<String>,
+
+    /// Record mouse motion as the distance moved instead of absolute positions
+    ///
+    /// Relative motion is played back from wherever the pointer is at the time of playback. This
+    /// is useful for games that capture the pointer or when the screen layout differs between
+    /// recording and playback.
+    #[arg(short, long)]
+    pub relative_motion: bool,
+
+    /// Backend used to listen to input events
+    ///
+    /// The uinput backend reads directly from the input devices and therefore also works on
+    /// Wayland and without a display server. Mouse motion is always recorded as relative motion
+    /// with this backend.
+    #[arg(long, value_enum, default_value_t)]
+    pub backend: Backend,
+
+    /// Only record events from the given input device
+    ///
+    /// The device is either the path of the device, the name of its event node like `event3` or
+    /// the name of the device as shown by `mkrp devices`. Can be given multiple times to record
+    /// from several devices. The device of every event is stored in the session. Requires the
+    /// uinput backend.
+    #[arg(short, long, value_name = "DEVICE")]
+    pub device: Vec<String>,
+
     /// Output recorded events into path.
     #[arg(value_name = "PATH")]
     pub output: PathBuf,
@@ -58,9 +127,24 @@ pub struct Record {
 )]
 pub struct Play {
     /// Number of iterations to be executed
-    #[arg(short, long)]
+    #[arg(short, long, conflicts_with_all = ["forever", "duration"])]
     pub iterations: Option<u32>,
 
+    /// Repeat the session until the stop key is pressed
+    #[arg(long = "loop", conflicts_with = "duration")]
+    pub forever: bool,
+
+    /// Repeat the session until the given amount of time has passed
+    ///
+    /// By default the iteration that crosses the limit is played until the end. Use `--immediate`
+    /// to stop as soon as the limit is reached.
+    #[arg(long, value_name = "DURATION", value_parser = parse_duration)]
+    pub duration: Option<Duration>,
+
+    /// Stop as soon as the `--duration` limit is reached instead of finishing the iteration
+    #[arg(long, requires = "duration")]
+    pub immediate: bool,
+
     /// Key to be used to stop playback
     ///
     /// The stop key can be any combination of keys that have to be either pressed or held down at
@@ -72,11 +156,390 @@ pub struct Play {
     #[arg(short, long)]
     pub stop_key: Option<String>,
 
+    /// Start playback at the given position
+    ///
+    /// The position is either an event index like `42`, a point in time like `1m30s` or the name
+    /// of a marker. Keys and buttons that are held down at that position are pressed before
+    /// playback starts.
+    #[arg(long, value_name = "POSITION")]
+    pub from: Option<Position>,
+
+    /// Stop playback at the given position
+    ///
+    /// Accepts the same values as `--from`. The event at the position itself is not played.
+    #[arg(long, value_name = "POSITION")]
+    pub to: Option<Position>,
+
     /// Delay between two iterations
     #[arg(short, long)]
     pub delay: Option<u64>,
 
+    /// Playback speed multiplier
+    ///
+    /// A value of `2.0` plays the session twice as fast while `0.5` plays it at half the speed.
+    /// The delay between iterations is not affected.
+    #[arg(long, value_name = "FACTOR", default_value_t = 1.0)]
+    pub speed: f64,
+
+    /// Maximum delay between two events
+    ///
+    /// Any delay longer than this is shortened to the given duration, compressing idle gaps in the
+    /// session. The cap is applied after the speed multiplier.
+    #[arg(long, value_name = "DURATION", value_parser = parse_duration)]
+    pub max_delay: Option<Duration>,
+
+    /// Scale mouse coordinates from the recorded display to the current display
+    #[arg(long, conflicts_with = "resolution")]
+    pub fit_display: bool,
+
+    /// Scale mouse coordinates from the recorded display to the given resolution
+    ///
+    /// Example:
+    ///     Play a session recorded on a 2560x1440 display on a 1080p display with `1920x1080`.
+    #[arg(long, value_name = "WIDTHxHEIGHT", value_parser = parse_resolution)]
+    pub resolution: Option<(u64, u64)>,
+
+    /// Move all mouse coordinates by the given offset in pixels
+    ///
+    /// The offset is applied after scaling. Example: `100,-20`.
+    #[arg(long, value_name = "X,Y", value_parser = parse_offset, allow_hyphen_values = true)]
+    pub offset: Option<(f64, f64)>,
+
+    /// Play the session relative to the current position of the mouse pointer
+    ///
+    /// All mouse coordinates are moved so that the first position in the session is where the
+    /// pointer is when playback starts.
+    #[arg(long, conflicts_with = "offset")]
+    pub relative: bool,
+
+    /// Keep mouse coordinates within the bounds of the target display
+    ///
+    /// The bounds are the `--resolution` if given, otherwise the current display.
+    #[arg(long)]
+    pub clamp: bool,
+
+    /// Randomly vary the delay of every event
+    ///
+    /// The amount is either a percentage of the delay of each event like `10%` or a fixed duration
+    /// like `20ms`. Delays never become negative so the order of events is always kept.
+    #[arg(long, value_name = "AMOUNT")]
+    pub jitter: Option<Jitter>,
+
+    /// Distribution the `--jitter` variation is drawn from
+    #[arg(long, value_enum, value_name = "DISTRIBUTION", default_value_t)]
+    pub jitter_distribution: Distribution,
+
+    /// Randomly move every click by up to the given number of pixels
+    #[arg(long, value_name = "PIXELS")]
+    pub click_jitter: Option<f64>,
+
+    /// Seed used for random variations to make them reproducible
+    ///
+    /// If no seed is passed a random one is used and printed before playback starts.
+    #[arg(long)]
+    pub seed: Option<u64>,
+
+    /// Walk through the session without simulating any input
+    ///
+    /// Every event is printed with its timestamp instead of being simulated.
+    #[arg(long, conflicts_with = "trace")]
+    pub dry_run: bool,
+
+    /// Do not wait for the delay between events during a dry run
+    #[arg(long, requires = "dry_run")]
+    pub no_delay: bool,
+
+    /// Print every event with its timestamp while it is simulated
+    #[arg(long)]
+    pub trace: bool,
+
+    /// Write the playback timing report as JSON into path
+    #[arg(long, value_name = "PATH")]
+    pub report: Option<PathBuf>,
+
+    /// Backend used to simulate input events
+    ///
+    /// The uinput backend injects events through a virtual device and therefore also works on
+    /// Wayland and without a display server. If the size of the display cannot be queried the
+    /// `--resolution` or the resolution the session was recorded on is used.
+    #[arg(long, value_enum, default_value_t)]
+    pub backend: Backend,
+
     /// Input file to be played
     #[arg(value_name = "PATH")]
     pub output: PathBuf,
 }
+
+/// Step through a recorded file interactively
+#[derive(Debug, Args)]
+#[command(disable_colored_help(true), disable_version_flag(true))]
+pub struct Debug {
+    /// Step through the session without simulating any input
+    #[arg(long)]
+    pub dry_run: bool,
+
+    /// Backend used to simulate input events
+    #[arg(long, value_enum, default_value_t)]
+    pub backend: Backend,
+
+    /// Input file to be debugged
+    #[arg(value_name = "PATH")]
+    pub input: PathBuf,
+}
+
+/// Type text into the focused window
+///
+/// The text is converted to key strokes using the keyboard layout given with `--layout`, which
+/// has to match the layout of the system the text is typed on.
+#[derive(Debug, Args)]
+#[command(
+    visible_alias("t"),
+    disable_colored_help(true),
+    disable_version_flag(true)
+)]
+pub struct Type {
+    /// Milliseconds to wait between characters
+    #[arg(short, long, value_name = "MS", default_value_t = 10)]
+    pub delay: u64,
+
+    /// Milliseconds every key is held down
+    #[arg(long, value_name = "MS", default_value_t = 0)]
+    pub hold: u64,
+
+    /// Keyboard layout of the system the text is typed on
+    #[arg(short, long, value_enum, default_value_t)]
+    pub layout: Layout,
+
+    /// Read the text from a file
+    #[arg(short, long, value_name = "PATH", conflicts_with = "text")]
+    pub file: Option<PathBuf>,
+
+    /// Backend used to simulate input events
+    #[arg(long, value_enum, default_value_t)]
+    pub backend: Backend,
+
+    /// Text to type. Read from stdin if neither the text nor a file is given
+    #[arg(value_name = "TEXT")]
+    pub text: Option<String>,
+}
+
+/// Print the text typed by the key events of a session
+///
+/// Shift, caps lock, dead keys, backspace, delete and the arrow, home and end keys are taken into
+/// account. Mouse clicks that move the cursor are not, so the result is an approximation.
+#[derive(Debug, Args)]
+#[command(disable_colored_help(true), disable_version_flag(true))]
+pub struct Text {
+    /// Keyboard layout the session was recorded on
+    #[arg(short, long, value_enum, default_value_t)]
+    pub layout: Layout,
+
+    /// Input session file
+    #[arg(value_name = "PATH")]
+    pub input: PathBuf,
+}
+
+/// Print an overview of a session without playing it
+///
+/// Shows the duration, the number of events of each type, how often every key is pressed, the
+/// longest idle gap, the area covered by the mouse and anything still held down at the end.
+#[derive(Debug, Args)]
+#[command(
+    visible_alias("i"),
+    disable_colored_help(true),
+    disable_version_flag(true)
+)]
+pub struct Inspect {
+    /// Input session file
+    #[arg(value_name = "PATH")]
+    pub input: PathBuf,
+}
+
+/// Check sessions for structural problems
+///
+/// Reports lines that cannot be parsed, releases without a matching press, presses that are never
+/// released, duplicate presses, mouse moves outside of the recorded display, unknown keys and
+/// empty sessions. Exits with a non-zero status if any problem is found.
+///
+/// With `--fix` unbalanced sessions are repaired and written back to their files. Every change
+/// is reported and problems that cannot be fixed are reported as usual.
+#[derive(Debug, Args)]
+#[command(disable_colored_help(true), disable_version_flag(true))]
+pub struct Check {
+    /// Repair unbalanced sessions in place
+    ///
+    /// Presses of the stop key at the end of the session, releases without a press and
+    /// duplicate presses are removed. Releases are added at the end for anything still held.
+    #[arg(long)]
+    pub fix: bool,
+
+    /// Key combination that was used to stop the recording
+    ///
+    /// Presses of these keys at the end of the session are removed by `--fix`. If no value is
+    /// passed this is defaulted to `Escape`.
+    #[arg(short, long, requires = "fix")]
+    pub stop_key: Option<String>,
+
+    /// Session files to check
+    #[arg(value_name = "PATH", required = true)]
+    pub inputs: Vec<PathBuf>,
+}
+
+/// Remove the idle time at the start and the end of a session
+///
+/// The delay before the first key press, click or scroll and all events after the last key or
+/// button event are removed.
+#[derive(Debug, Args)]
+#[command(disable_colored_help(true), disable_version_flag(true))]
+pub struct Trim {
+    /// Maximum delay between two events
+    ///
+    /// Any delay longer than this is shortened to the given duration, compressing idle gaps in the
+    /// session.
+    #[arg(long, value_name = "DURATION", value_parser = parse_duration)]
+    pub max_delay: Option<Duration>,
+
+    /// Input session file
+    #[arg(value_name = "INPUT")]
+    pub input: PathBuf,
+
+    /// File the trimmed session is written to
+    #[arg(value_name = "OUTPUT")]
+    pub output: PathBuf,
+}
+
+/// Remove a range of events from a session
+///
+/// Keys and buttons held down at the start of the range are released before it and pressed
+/// again after it, so the session stays balanced.
+#[derive(Debug, Args)]
+#[command(disable_colored_help(true), disable_version_flag(true))]
+pub struct Cut {
+    /// Start of the range to remove
+    ///
+    /// The position is either an event index like `42`, a point in time like `1m30s` or the name
+    /// of a marker. Defaults to the start of the session.
+    #[arg(long, value_name = "POSITION")]
+    pub from: Option<Position>,
+
+    /// End of the range to remove
+    ///
+    /// Accepts the same values as `--from`. The event at the position itself is kept. Defaults to
+    /// the end of the session.
+    #[arg(long, value_name = "POSITION")]
+    pub to: Option<Position>,
+
+    /// Input session file
+    #[arg(value_name = "INPUT")]
+    pub input: PathBuf,
+
+    /// File the resulting session is written to
+    #[arg(value_name = "OUTPUT")]
+    pub output: PathBuf,
+}
+
+/// Write a range of events of a session into a new file
+///
+/// Keys and buttons held down at the start of the range are pressed at the start of the new
+/// session and everything still held at its end is released.
+#[derive(Debug, Args)]
+#[command(disable_colored_help(true), disable_version_flag(true))]
+pub struct Extract {
+    /// Start of the range to extract
+    ///
+    /// The position is either an event index like `42`, a point in time like `1m30s` or the name
+    /// of a marker. Defaults to the start of the session.
+    #[arg(long, value_name = "POSITION")]
+    pub from: Option<Position>,
+
+    /// End of the range to extract
+    ///
+    /// Accepts the same values as `--from`. The event at the position itself is not extracted.
+    /// Defaults to the end of the session.
+    #[arg(long, value_name = "POSITION")]
+    pub to: Option<Position>,
+
+    /// Input session file
+    #[arg(value_name = "INPUT")]
+    pub input: PathBuf,
+
+    /// File the extracted session is written to
+    #[arg(value_name = "OUTPUT")]
+    pub output: PathBuf,
+}
+
+/// Join several sessions into one
+///
+/// Anything still held down at the end of a session is released before the next one starts.
+/// Mouse coordinates are scaled to the display of the first session that has one.
+#[derive(Debug, Args)]
+#[command(disable_colored_help(true), disable_version_flag(true))]
+pub struct Concat {
+    /// Delay between the end of a session and the start of the next one
+    ///
+    /// The duration is a sequence of numbers with units such as `1h`, `5m`, `1m30s` or `500ms`.
+    /// A number without a unit is treated as seconds.
+    #[arg(long, value_name = "DURATION", value_parser = parse_duration, default_value = "0s")]
+    pub gap: Duration,
+
+    /// File the joined session is written to
+    #[arg(short, long, value_name = "PATH")]
+    pub output: PathBuf,
+
+    /// Session files to join in the given order
+    #[arg(value_name = "PATH", required = true)]
+    pub inputs: Vec<PathBuf>,
+}
+
+/// List the keyboards and mice that can be recorded from with the uinput backend
+#[derive(Debug, Args)]
+#[command(disable_colored_help(true), disable_version_flag(true))]
+pub struct Devices {}
+
+/// Parse a display resolution in the form of `WIDTHxHEIGHT`.
+pub fn parse_resolution(s: &str) -> Result<(u64, u64), String> {
+    let (width, height) = s
+        .split_once('x')
+        .ok_or_else(|| format!("expected WIDTHxHEIGHT but got '{}'", s))?;
+    let width = width
+        .parse::<u64>()
+        .map_err(|_| format!("invalid width in '{}'", s))?;
+    let height = height
+        .parse::<u64>()
+        .map_err(|_| format!("invalid height in '{}'", s))?;
+    if width == 0 || height == 0 {
+        return Err(format!("resolution '{}' must not be empty", s));
+    }
+    Ok((width, height))
+}
+
+/// Parse a pixel offset in the form of `X,Y`.
+pub fn parse_offset(s: &str) -> Result<(f64, f64), String> {
+    let (x, y) = s
+        .split_once(',')
+        .ok_or_else(|| format!("expected X,Y but got '{}'", s))?;
+    let x = x
+        .trim()
+        .parse::<f64>()
+        .map_err(|_| format!("invalid x offset in '{}'", s))?;
+    let y = y
+        .trim()
+        .parse::<f64>()
+        .map_err(|_| format!("invalid y offset in '{}'", s))?;
+    Ok((x, y))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_resolutions_and_offsets() {
+        assert_eq!(parse_resolution("1920x1080"), Ok((1920, 1080)));
+        assert!(parse_resolution("1920").is_err());
+        assert!(parse_resolution("0x1080").is_err());
+
+        assert_eq!(parse_offset("100,-20"), Ok((100.0, -20.0)));
+        assert!(parse_offset("100").is_err());
+    }
+}
@@ -0,0 +1,63 @@
+use mkrp::{
+    lint::{check, event_lines, fix},
+    Session,
+};
+
+use crate::cli::{Check, Run};
+
+impl Run for Check {
+    fn run(self) -> eyre::Result<()> {
+        let stop_key = super::parse_keys(self.stop_key.as_deref(), "stop key")?;
+
+        let mut problems = 0;
+        for path in &self.inputs {
+            let mut contents = std::fs::read_to_string(path)?;
+            let mut session = match contents.parse::<Session>() {
+                Ok(session) => session,
+                Err(e) => {
+                    println!("{}:{}: {}", path.display(), e.line, e.message);
+                    problems += 1;
+                    continue;
+                }
+            };
+
+            if self.fix {
+                let (fixed, changes) = fix(&session, stop_key);
+                if !changes.is_empty() {
+                    let lines = event_lines(&contents);
+                    for change in &changes {
+                        match change.index() {
+                            Some(index) => {
+                                println!("{}:{}: {}", path.display(), lines[index], change)
+                            }
+                            None => println!("{}: {}", path.display(), change),
+                        }
+                    }
+                    fixed.to_file(path)?;
+                    contents = fixed.to_string();
+                    session = fixed;
+                }
+            }
+
+            let lines = event_lines(&contents);
+            for diagnostic in check(&session) {
+                match diagnostic.index {
+                    Some(index) => println!(
+                        "{}:{}: {}",
+                        path.display(),
+                        lines[index],
+                        diagnostic.problem
+                    ),
+                    None => println!("{}: {}", path.display(), diagnostic.problem),
+                }
+                problems += 1;
+            }
+        }
+
+        if problems > 0 {
+            println!("{} problems found", problems);
+            std::process::exit(1);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,25 @@
+use mkrp::{schedule::format_timestamp, Session};
+
+use crate::cli::{Concat, Run};
+
+impl Run for Concat {
+    fn run(self) -> eyre::Result<()> {
+        let mut inputs = self.inputs.iter();
+        let mut session = match inputs.next() {
+            Some(path) => Session::from_file(path)?,
+            None => return Ok(()),
+        };
+        for path in inputs {
+            session.append(Session::from_file(path)?, self.gap);
+        }
+        session.to_file(&self.output)?;
+
+        println!(
+            "Joined {} sessions into {} events and {}",
+            self.inputs.len(),
+            session.events.len(),
+            format_timestamp(session.total_time)
+        );
+        Ok(())
+    }
+}
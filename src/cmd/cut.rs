@@ -0,0 +1,13 @@
+use mkrp::Session;
+
+use crate::cli::{Cut, Run};
+
+impl Run for Cut {
+    fn run(self) -> eyre::Result<()> {
+        let session = Session::from_file(&self.input)?;
+        let (start, end) = super::resolve_range(&session, &self.from, &self.to)?;
+        session.cut(start, end).to_file(&self.output)?;
+        println!("Removed events {} to {}", start, end);
+        Ok(())
+    }
+}
@@ -0,0 +1,401 @@
+use std::{
+    io::{stdin, stdout, BufRead, Write},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread,
+    time::Duration,
+};
+
+use crossbeam_channel::{bounded, select, unbounded};
+
+use mkrp::{
+    backend::{InputSink, MockSink},
+    schedule::{format_timestamp, Timeline},
+    session::{InputState, Position, Session},
+    Event, EventType,
+};
+
+use crate::cli::{Debug, Run};
+
+const HELP: &str = "\
+Commands:
+    s, step [N]         Execute the next N events without delay (default 1)
+    n, next             Execute events until the next marker
+    k, key              Execute events up to and including the next key press
+    c, continue         Play events in real time until the next breakpoint or Ctrl-C
+    b, break [POS]      Set a breakpoint at an event index, time or marker. Lists breakpoints
+                        if no position is given
+    d, delete [POS]     Delete the breakpoint at a position or all breakpoints
+    i, info             Show the current position and held keys and buttons
+    h, help             Show this message
+    q, quit             Release held keys and buttons and exit";
+
+struct Debugger {
+    session: Session,
+    /// Index of the next event to be executed
+    index: usize,
+    /// Time from the start of the session until the event before `index` was executed
+    time: Duration,
+    state: InputState,
+    breakpoints: Vec<usize>,
+    sink: Box<dyn InputSink>,
+    interrupted: Arc<AtomicBool>,
+}
+
+impl Debugger {
+    fn new(session: Session, sink: Box<dyn InputSink>) -> Self {
+        Self {
+            session,
+            index: 0,
+            time: Duration::ZERO,
+            state: InputState::default(),
+            breakpoints: Vec::new(),
+            sink,
+            interrupted: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    fn is_done(&self) -> bool {
+        self.index >= self.session.events.len()
+    }
+
+    fn print_next(&self) {
+        match self.session.events.get(self.index) {
+            Some(event) => println!(
+                "next: {} {:>6} {}",
+                format_timestamp(self.time + event.delay),
+                self.index,
+                event.event
+            ),
+            None => println!("end of session"),
+        }
+    }
+
+    fn print_info(&self) {
+        println!(
+            "position: {} / {} at {}",
+            self.index,
+            self.session.events.len(),
+            format_timestamp(self.time)
+        );
+
+        let keys: Vec<_> = self.state.keys.iter().map(|k| k.to_str()).collect();
+        let buttons: Vec<_> = self.state.buttons.iter().map(|b| b.to_str()).collect();
+        println!("keys:     {}", keys.join(", "));
+        println!("buttons:  {}", buttons.join(", "));
+        if let Some((x, y)) = self.state.position {
+            println!("mouse:    {},{}", x, y);
+        }
+
+        for marker in self
+            .session
+            .markers
+            .iter()
+            .filter(|m| m.index == self.index)
+        {
+            println!("marker:   {}", marker.name);
+        }
+        self.print_next();
+    }
+
+    /// Execute the next event and advance the position.
+    fn execute(&mut self) {
+        let Event { delay, event, .. } = self.session.events[self.index];
+        self.time += delay;
+        println!(
+            "{} {:>6} {}",
+            format_timestamp(self.time),
+            self.index,
+            event
+        );
+
+        if self.sink.simulate(&event).is_err() {
+            println!("failed to simulate {}", event);
+        }
+
+        self.state.apply(&event);
+        self.index += 1;
+    }
+
+    /// Execute the next `count` events without delay.
+    fn step(&mut self, count: usize) {
+        for _ in 0..count {
+            if self.is_done() {
+                break;
+            }
+            self.execute();
+        }
+    }
+
+    /// Execute events without delay up to and including the next key press.
+    fn next_key(&mut self) {
+        while !self.is_done() {
+            let event = self.session.events[self.index].event;
+            self.execute();
+            if matches!(event, EventType::KeyPress(_)) || self.breakpoints.contains(&self.index) {
+                break;
+            }
+        }
+    }
+
+    fn next_marker(&mut self) {
+        let target = self
+            .session
+            .markers
+            .iter()
+            .map(|m| m.index)
+            .find(|&i| i > self.index)
+            .unwrap_or(self.session.events.len());
+        while !self.is_done() && self.index < target {
+            self.execute();
+            if self.breakpoints.contains(&self.index) {
+                break;
+            }
+        }
+    }
+
+    /// Play events with their recorded delays until a breakpoint is reached.
+    fn resume(&mut self) {
+        self.interrupted.store(false, Ordering::SeqCst);
+        let mut timeline = Timeline::start();
+        while !self.is_done() {
+            if self.interrupted.load(Ordering::SeqCst) {
+                println!("interrupted");
+                break;
+            }
+
+            timeline.wait(self.session.events[self.index].delay);
+            self.execute();
+            if self.breakpoints.contains(&self.index) {
+                println!("breakpoint at {}", self.index);
+                break;
+            }
+        }
+    }
+
+    fn release_all(&mut self) {
+        let releases = self
+            .state
+            .keys
+            .iter()
+            .map(EventType::KeyRelease)
+            .chain(self.state.buttons.iter().map(EventType::ButtonRelease));
+        for event in releases {
+            if self.sink.simulate(&event).is_err() {
+                println!("failed to simulate {}", event);
+            }
+        }
+        self.state = InputState::default();
+    }
+
+    fn resolve(&self, arg: &str) -> Option<usize> {
+        let position = arg.parse::<Position>().ok()?;
+        let index = self.session.index_of(&position);
+        if index.is_none() {
+            println!("unknown position '{}'", arg);
+        }
+        index
+    }
+
+    /// Run a command read from the prompt. Returns `false` if the debugger should exit.
+    fn command(&mut self, line: &str) -> bool {
+        let mut args = line.split_whitespace();
+        let Some(command) = args.next() else {
+            return true;
+        };
+        let arg = args.next();
+
+        match command {
+            "s" | "step" => {
+                let count = match arg.map(str::parse::<usize>) {
+                    Some(Ok(count)) => count,
+                    Some(Err(_)) => {
+                        println!("invalid number of steps");
+                        return true;
+                    }
+                    None => 1,
+                };
+                self.step(count);
+                self.print_next();
+            }
+            "n" | "next" => {
+                self.next_marker();
+                self.print_next();
+            }
+            "k" | "key" => {
+                self.next_key();
+                self.print_next();
+            }
+            "c" | "continue" => {
+                self.resume();
+                self.print_next();
+            }
+            "b" | "break" => match arg {
+                Some(arg) => {
+                    if let Some(index) = self.resolve(arg) {
+                        if !self.breakpoints.contains(&index) {
+                            self.breakpoints.push(index);
+                            self.breakpoints.sort();
+                        }
+                        println!("breakpoint set at {}", index);
+                    }
+                }
+                None => {
+                    for index in &self.breakpoints {
+                        println!(
+                            "{:>6} {}",
+                            index,
+                            format_timestamp(self.session.time_at(*index))
+                        );
+                    }
+                }
+            },
+            "d" | "delete" => match arg {
+                Some(arg) => {
+                    if let Some(index) = self.resolve(arg) {
+                        self.breakpoints.retain(|&b| b != index);
+                    }
+                }
+                None => self.breakpoints.clear(),
+            },
+            "i" | "info" => self.print_info(),
+            "h" | "help" => println!("{}", HELP),
+            "q" | "quit" => return false,
+            c => println!("unknown command '{}'", c),
+        }
+        true
+    }
+}
+
+impl Run for Debug {
+    fn run(self) -> eyre::Result<()> {
+        let session = Session::from_file(&self.input)?;
+        // A dry run does not simulate anything, so the real sink is not created
+        let sink: Box<dyn InputSink> = if self.dry_run {
+            Box::new(MockSink {
+                display: session.display,
+                ..Default::default()
+            })
+        } else {
+            self.backend.sink(session.display)?
+        };
+        let mut debugger = Debugger::new(session, sink);
+
+        // Ctrl-C interrupts `continue`, and exits like `quit` while waiting at the prompt
+        let (interrupt_tx, interrupt_rx) = bounded(1);
+        let interrupted = debugger.interrupted.clone();
+        ctrlc::set_handler(move || {
+            interrupted.store(true, Ordering::SeqCst);
+            interrupt_tx.try_send(()).ok();
+        })?;
+
+        // Lines are read on their own thread so the prompt can also wait for Ctrl-C
+        let (line_tx, line_rx) = unbounded();
+        thread::spawn(move || {
+            for line in stdin().lock().lines() {
+                if line_tx.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+
+        println!(
+            "{} events, {} markers, {}. Type `help` for a list of commands.",
+            debugger.session.events.len(),
+            debugger.session.markers.len(),
+            format_timestamp(debugger.session.total_time)
+        );
+        debugger.print_next();
+
+        loop {
+            // Ctrl-C pressed during the last command has already been handled
+            while interrupt_rx.try_recv().is_ok() {}
+
+            print!("(mkrp) ");
+            stdout().flush()?;
+
+            select! {
+                recv(line_rx) -> line => match line {
+                    Ok(line) => {
+                        if !debugger.command(&line?) {
+                            break;
+                        }
+                    }
+                    Err(_) => break,
+                },
+                recv(interrupt_rx) -> _ => {
+                    println!();
+                    break;
+                }
+            }
+        }
+
+        debugger.release_all();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use mkrp::Key;
+
+    use super::*;
+
+    fn debugger(contents: &str) -> (Debugger, MockSink) {
+        let sink = MockSink::default();
+        let session = Session::from_str(contents).unwrap();
+        (Debugger::new(session, Box::new(sink.clone())), sink)
+    }
+
+    #[test]
+    fn step_to_markers_and_keys() {
+        let (mut debugger, sink) =
+            debugger("10,mm,5,5\n20,kp,60\n0,kp,1\n#marker,m\n5,kr,1\n0,mp,1\n0,mr,1\n0,kr,60\n");
+
+        assert!(debugger.command("step 2"));
+        assert_eq!(debugger.index, 2);
+        assert_eq!(debugger.time, Duration::from_millis(30));
+        assert!(debugger.command("key"));
+        assert_eq!(debugger.index, 3);
+        assert!(debugger.command("next"));
+        assert_eq!(debugger.index, 7);
+        assert!(debugger.is_done());
+        assert!(debugger.command("step"));
+        assert_eq!(debugger.index, 7);
+        assert_eq!(debugger.time, debugger.session.total_time);
+
+        assert_eq!(sink.events().len(), 7);
+        assert_eq!(sink.events()[2], EventType::KeyPress(Key::A));
+        assert!(!debugger.command("quit"));
+    }
+
+    #[test]
+    fn stop_at_breakpoints_and_release() {
+        let (mut debugger, sink) = debugger("0,kp,60\n0,kp,1\n0,kr,1\n#marker,m\n0,kp,2\n0,kr,2\n");
+
+        debugger.command("break m");
+        debugger.command("break 1");
+        assert_eq!(debugger.breakpoints, vec![1, 3]);
+        debugger.command("continue");
+        assert_eq!(debugger.index, 1);
+        debugger.command("continue");
+        assert_eq!(debugger.index, 3);
+        debugger.command("delete 1");
+        assert_eq!(debugger.breakpoints, vec![3]);
+
+        debugger.release_all();
+        assert_eq!(
+            sink.events(),
+            vec![
+                EventType::KeyPress(Key::LCtrl),
+                EventType::KeyPress(Key::A),
+                EventType::KeyRelease(Key::A),
+                EventType::KeyRelease(Key::LCtrl),
+            ]
+        );
+    }
+}
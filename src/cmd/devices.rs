@@ -0,0 +1,24 @@
+use crate::cli::{Devices, Run};
+
+impl Run for Devices {
+    #[cfg(target_os = "linux")]
+    fn run(self) -> eyre::Result<()> {
+        let devices = mkrp::backend::input_devices();
+        if devices.is_empty() {
+            println!(
+                "No keyboard or mouse found. Check that the user is allowed to read /dev/input"
+            );
+        }
+        for device in devices {
+            println!("{}\t{}", device.path.display(), device.name);
+        }
+        Ok(())
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn run(self) -> eyre::Result<()> {
+        Err(eyre::eyre!(
+            "Listing input devices is only available on Linux"
+        ))
+    }
+}
@@ -0,0 +1,13 @@
+use mkrp::Session;
+
+use crate::cli::{Extract, Run};
+
+impl Run for Extract {
+    fn run(self) -> eyre::Result<()> {
+        let session = Session::from_file(&self.input)?;
+        let (start, end) = super::resolve_range(&session, &self.from, &self.to)?;
+        session.range(start, end).to_file(&self.output)?;
+        println!("Extracted events {} to {}", start, end);
+        Ok(())
+    }
+}
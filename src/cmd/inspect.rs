@@ -0,0 +1,21 @@
+use mkrp::{schedule::format_timestamp, stats::Stats, Session};
+
+use crate::cli::{Inspect, Run};
+
+impl Run for Inspect {
+    fn run(self) -> eyre::Result<()> {
+        let session = Session::from_file(&self.input)?;
+        if let Some((width, height)) = session.display {
+            println!("Display:          {}x{}", width, height);
+        }
+        print!("{}", Stats::new(&session));
+        for marker in &session.markers {
+            println!(
+                "Marker:           {} at {}",
+                marker.name,
+                format_timestamp(session.time_at(marker.index))
+            );
+        }
+        Ok(())
+    }
+}
@@ -1,11 +1,71 @@
+use mkrp::{session::Position, Key, KeyState, Session};
+
 use crate::cli::{Cli, Run};
 
+mod check;
+mod concat;
+mod cut;
+mod debug;
+mod devices;
+mod extract;
+mod inspect;
 mod play;
 mod record;
+mod text;
+mod trim;
+mod type_text;
 
 pub fn run(cli: Cli) -> eyre::Result<()> {
     match cli.command {
         crate::cli::Cmd::Record(c) => c.run(),
         crate::cli::Cmd::Play(c) => c.run(),
+        crate::cli::Cmd::Debug(c) => c.run(),
+        crate::cli::Cmd::Devices(c) => c.run(),
+        crate::cli::Cmd::Type(c) => c.run(),
+        crate::cli::Cmd::Text(c) => c.run(),
+        crate::cli::Cmd::Inspect(c) => c.run(),
+        crate::cli::Cmd::Check(c) => c.run(),
+        crate::cli::Cmd::Trim(c) => c.run(),
+        crate::cli::Cmd::Cut(c) => c.run(),
+        crate::cli::Cmd::Extract(c) => c.run(),
+        crate::cli::Cmd::Concat(c) => c.run(),
+    }
+}
+
+/// Parse a comma separated key combination, defaulting to `Escape` if none is given.
+fn parse_keys(keys: Option<&str>, what: &str) -> eyre::Result<KeyState> {
+    let Some(keys) = keys else {
+        return Ok(KeyState::with_pressed(&[Key::Escape]));
+    };
+
+    let mut state = KeyState::default();
+    for item in keys.split(',') {
+        let key = Key::from_str(item).ok_or(eyre::eyre!("Unknown key '{}' for {}", item, what))?;
+        state.set_pressed(key);
+    }
+    Ok(state)
+}
+
+/// Resolve an optional start and end position into a range of event indices, defaulting to the
+/// start and end of the session.
+fn resolve_range(
+    session: &Session,
+    from: &Option<Position>,
+    to: &Option<Position>,
+) -> eyre::Result<(usize, usize)> {
+    let resolve = |position: &Position| {
+        session
+            .index_of(position)
+            .ok_or(eyre::eyre!("Unknown position '{}' in session", position))
+    };
+    let start = from.as_ref().map(resolve).transpose()?.unwrap_or(0);
+    let end = to
+        .as_ref()
+        .map(resolve)
+        .transpose()?
+        .unwrap_or(session.events.len());
+    if start > end {
+        return Err(eyre::eyre!("Start of the range is after its end"));
     }
+    Ok((start, end))
 }
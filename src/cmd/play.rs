@@ -1,150 +1,187 @@
-use std::{
-    hint::spin_loop,
-    sync::mpsc::channel,
-    thread,
-    time::{Duration, Instant},
-};
+use std::{fs::File, thread, time::Duration, time::Instant};
 
-use crossbeam_channel::{bounded, select, tick, unbounded, Receiver};
+use crossbeam_channel::{tick, TryRecvError};
 use indicatif::{FormattedDuration, MultiProgress, ProgressBar, ProgressStyle};
-use rdev::{listen, simulate, EventType};
-
-use crate::{
-    cli::{Play, Run},
-    keys::{Key, KeyState},
-    mouse::MouseState,
-    session::Session,
+use mkrp::{
+    backend::{InputSink, MockSink},
+    humanize::Humanizer,
+    player::Limit,
+    schedule::format_timestamp,
+    EventType, KeyState, Player, PlayerEvent, Session,
 };
 
-enum UiEvent {
-    Iteration(u32),
-    Event(u32),
-    Aborted,
-    Completed,
-}
+use crate::cli::{Play, Run};
 
 impl Run for Play {
     fn run(self) -> eyre::Result<()> {
-        let session = Session::from_file(self.output).unwrap();
-
-        let stop_state = match self.stop_key {
-            Some(s) => {
-                let mut state = KeyState::default();
-                for item in s.split(',') {
-                    let key = Key::from_str(item)
-                        .ok_or(eyre::eyre!("Unknown key '{}' for stop key", item))?;
-                    state.set_pressed(key);
-                }
-                state
-            }
-            None => KeyState::with_pressed(&[Key::Escape]),
+        if !(self.speed.is_finite() && self.speed > 0.0) {
+            return Err(eyre::eyre!("Speed must be a positive number"));
+        }
+        if self
+            .click_jitter
+            .is_some_and(|pixels| !(pixels.is_finite() && pixels >= 0.0))
+        {
+            return Err(eyre::eyre!(
+                "Click jitter must be zero or a positive number"
+            ));
+        }
+
+        let mut session = Session::from_file(self.output)?;
+        if self.from.is_some() || self.to.is_some() {
+            let (start, end) = super::resolve_range(&session, &self.from, &self.to)?;
+            session = session.range(start, end);
+        }
+        session.set_speed(self.speed);
+        if let Some(max) = self.max_delay {
+            session.cap_delay(max);
+        }
+
+        // A dry run does not simulate anything, so the real sink is not created. The pointer is
+        // placed at the start of the session to make `--relative` a no-op.
+        let sink: Box<dyn InputSink> = if self.dry_run {
+            Box::new(MockSink {
+                position: session.first_position(),
+                display: self.resolution.or(session.display),
+                ..Default::default()
+            })
+        } else {
+            self.backend.sink(self.resolution.or(session.display))?
+        };
+        let current_display = || {
+            sink.display_size()
+                .ok_or(eyre::eyre!("Could not get the size of the display"))
+        };
+        let target_display = if self.fit_display {
+            Some(current_display()?)
+        } else {
+            self.resolution
+        };
+        if let Some(target) = target_display {
+            let recorded = session.display.ok_or(eyre::eyre!(
+                "The session does not contain the resolution it was recorded on"
+            ))?;
+            session.scale(recorded, target);
+            session.display = Some(target);
+        }
+        if let Some((x, y)) = self.offset {
+            session.translate(x, y);
+        }
+        if self.relative {
+            let position = sink.position().ok_or(eyre::eyre!(
+                "Could not get the position of the mouse pointer"
+            ))?;
+            session.translate_to(position);
+        }
+        if self.clamp {
+            session.clamp(match target_display {
+                Some(target) => target,
+                None => current_display()?,
+            });
+        }
+
+        let humanizer = if self.jitter.is_some() || self.click_jitter.is_some() {
+            let seed = self.seed.unwrap_or_else(rand::random);
+            println!("Humanize seed: {}", seed);
+            Some(Humanizer::new(
+                seed,
+                self.jitter,
+                self.jitter_distribution,
+                self.click_jitter,
+            ))
+        } else {
+            None
         };
 
-        let total_iterations = self.iterations.unwrap_or(1);
+        let stop_state = super::parse_keys(self.stop_key.as_deref(), "stop key")?;
+
+        let limit = match (self.forever, self.duration) {
+            (true, _) => Limit::Forever,
+            (false, Some(duration)) => Limit::Duration {
+                duration,
+                immediate: self.immediate,
+            },
+            (false, None) => Limit::Iterations(self.iterations.unwrap_or(1)),
+        };
         let delay = self
             .delay
             .map(Duration::from_millis)
             .unwrap_or(Duration::ZERO);
-        let has_iteration_delay = delay.is_zero();
+        if matches!(limit, Limit::Forever) && session.total_time.is_zero() && delay.is_zero() {
+            return Err(eyre::eyre!(
+                "A session with a duration of zero can only be looped with a `--delay`"
+            ));
+        }
         let total_session = session.events.len();
 
-        let total_duration =
-            (session.total_time * total_iterations) + (delay * (total_iterations - 1));
+        let total_duration = match limit {
+            Limit::Iterations(n) => (session.total_time * n) + (delay * n.saturating_sub(1)),
+            Limit::Duration { duration, .. } => duration,
+            Limit::Forever => Duration::ZERO,
+        };
         let total_formatted_duration = FormattedDuration(total_duration);
         let session_duration = session.total_time;
         let session_formatted_dutation = FormattedDuration(session.total_time);
 
-        // Terminate channel
-        let (tt, rt) = unbounded();
-        // Execution channel
-        let (tx, rx) = unbounded();
-
-        let tt_input = tt.clone();
-        let listener = thread::spawn(move || {
-            let tt = tt_input;
-            let mut keystate = KeyState::default();
-            listen(move |event| match event.event_type {
-                EventType::KeyPress(k) => {
-                    keystate.set_pressed(k.into());
-                    if keystate.is_state_held(stop_state) {
-                        println!("sending terminate state");
-                        tt.send(true)
-                            .unwrap_or_else(|_| println!("Could not send terminate event"));
-                    }
-                }
-                EventType::KeyRelease(k) => {
-                    keystate.set_released(k.into());
-                }
-                _ => {}
-            })
-            .expect("Could not listen");
-        });
+        // The stop key is not needed for a dry run, which does not take over the input
+        let source = match self.dry_run {
+            true => None,
+            false => Some(self.backend.source(&[])?),
+        };
 
-        let executor_rt = rt.clone();
-        let executor = thread::spawn(move || {
-            let rt = executor_rt;
-            let mut keys_state = KeyState::default();
-            let mut mouse_state = MouseState::default();
-            'outer: for current_iteration in 0..total_iterations {
-                for (i, event) in session.events.iter().enumerate() {
-                    if rt.try_recv().is_ok() {
-                        break 'outer;
-                    }
+        let mut player = Player::new(session)
+            .limit(limit)
+            .delay(delay)
+            .dry_run(self.dry_run)
+            .no_delay(self.no_delay);
+        if let Some(humanizer) = humanizer {
+            player = player.humanizer(humanizer);
+        }
+        let handle = player.start(sink);
+        let trace = self.trace || self.dry_run;
 
-                    match event.event {
-                        EventType::KeyPress(k) => keys_state.set_pressed(k.into()),
-                        EventType::KeyRelease(k) => keys_state.set_released(k.into()),
-                        EventType::ButtonPress(b) => mouse_state.set_pressed(b.into()),
-                        EventType::ButtonPress(b) => mouse_state.set_released(b.into()),
-                        _ => {}
+        let mut listener = source.map(|source| {
+            let control = handle.control();
+            thread::spawn(move || {
+                let mut keystate = KeyState::default();
+                source.listen(Box::new(move |event| match event.event {
+                    EventType::KeyPress(k) => {
+                        keystate.set_pressed(k);
+                        if keystate.is_state_held(stop_state) {
+                            println!("sending terminate state");
+                            control.stop();
+                        }
                     }
-
-                    spin_sleep::sleep(event.delay);
-                    simulate(&event.event)
-                        .unwrap_or_else(|_| panic!("failed to simulate {:#?}", event));
-                    tx.send(UiEvent::Event(i as u32 + 1))
-                        .expect("failed to send event iteration to main ui thread");
-                }
-
-                if current_iteration < total_iterations - 1 && has_iteration_delay {
-                    spin_sleep::sleep(delay);
-                }
-
-                tx.send(UiEvent::Iteration(current_iteration + 1))
-                    .expect("failed to send iteration event to main ui thread");
-            }
-
-            // Checking the state of keys and mouse and unset anything that is recorded as pressed
-            for key in keys_state {
-                let event = EventType::KeyRelease(key.into());
-                simulate(&event).unwrap_or_else(|_| panic!("failed to simulate {:#?}", event));
-            }
-
-            for button in mouse_state {
-                let event = EventType::ButtonRelease(button.into());
-                simulate(&event).unwrap_or_else(|_| panic!("failed to simulate {:#?}", event));
-            }
-
-            tx.send(UiEvent::Completed)
-                .expect("failed to send Completed event to ui thread");
+                    EventType::KeyRelease(k) => {
+                        keystate.set_released(k);
+                    }
+                    _ => {}
+                }))
+            })
         });
 
-        let mut current_iteration = 1;
-        let mut current_event = 1;
         let zero_duration = FormattedDuration(Duration::ZERO);
 
         // Progressbar setup
         let style =
             ProgressStyle::with_template("{prefix} {wide_bar} {msg} {percent:>3}%").unwrap();
         let mp = MultiProgress::new();
-        let tpb = mp.add(
-            ProgressBar::new(total_duration.as_secs())
-                .with_style(style.clone())
-                .with_prefix("Total  ")
-                .with_message(format!("{} / {}", zero_duration, total_formatted_duration)),
-        );
+        // Without a known end the total bar only shows the iteration count and elapsed time
+        let tpb = match limit {
+            Limit::Forever => mp.add(
+                ProgressBar::new_spinner()
+                    .with_style(ProgressStyle::with_template("{prefix} {spinner} {msg}").unwrap())
+                    .with_prefix("Total  ")
+                    .with_message(format!("[{}]", zero_duration)),
+            ),
+            _ => mp.add(
+                ProgressBar::new(total_duration.as_secs())
+                    .with_style(style.clone())
+                    .with_prefix("Total  ")
+                    .with_message(format!("{} / {}", zero_duration, total_formatted_duration)),
+            ),
+        };
         let spb = mp.add(
-            ProgressBar::new(session.total_time.as_secs())
+            ProgressBar::new(session_duration.as_secs())
                 .with_style(style.clone())
                 .with_prefix("Session")
                 .with_message(format!(
@@ -161,31 +198,49 @@ impl Run for Play {
         let mut current_event = 1;
 
         // register ctrl-c handler
-        ctrlc::set_handler(move || tt.send(true).expect("Failed to send terminate signal"));
+        let control = handle.control();
+        ctrlc::set_handler(move || control.stop())?;
 
         let mut finished_successfull = true;
+        let mut listen_error = None;
         loop {
-            if rt.try_recv().is_ok() {
-                finished_successfull = false;
-                break;
+            // Without the listener the stop key does not work, so playback is stopped as well
+            if listener.as_ref().is_some_and(|l| l.is_finished()) {
+                if let Some(Ok(Err(e))) = listener.take().map(|l| l.join()) {
+                    handle.control().stop();
+                    listen_error = Some(e.wrap_err("Could not listen for the stop key"));
+                }
             }
 
-            if let Ok(event) = rx.try_recv() {
-                match event {
-                    UiEvent::Iteration(n) => {
+            match handle.events().try_recv() {
+                Ok(event) => match event {
+                    PlayerEvent::Iteration(n) => {
                         current_total = n + 1;
                         spb.set_position(0);
                         session_start = Instant::now();
                     }
-                    UiEvent::Event(n) => {
-                        current_event = n + 1;
+                    PlayerEvent::Event { index, time, event } => {
+                        current_event = index + 2;
+                        if trace {
+                            mp.println(format!(
+                                "{} {:>6} {}",
+                                format_timestamp(time),
+                                index,
+                                event
+                            ))
+                            .ok();
+                        }
                     }
-                    UiEvent::Aborted => break,
-                    UiEvent::Completed => break,
-                }
+                    PlayerEvent::Stopped => finished_successfull = false,
+                    PlayerEvent::Completed => break,
+                    PlayerEvent::Paused | PlayerEvent::Resumed => {}
+                },
+                Err(TryRecvError::Empty) => {}
+                // The player thread ended without completing, `wait` reports why
+                Err(TryRecvError::Disconnected) => break,
             }
 
-            if let Ok(tick) = ticker.try_recv() {
+            if ticker.try_recv().is_ok() {
                 let now = Instant::now();
                 tpb.inc(1);
                 spb.inc(1);
@@ -196,14 +251,28 @@ impl Run for Play {
                 let cur_session_duration = now.saturating_duration_since(session_start);
                 let cur_session_eta = session_duration.saturating_sub(cur_session_duration);
 
-                tpb.set_message(format!(
-                    "({:>5}/{:>5}) [{} / {}] ({})",
-                    current_total,
-                    total_iterations,
-                    FormattedDuration(cur_total_duration),
-                    total_formatted_duration,
-                    FormattedDuration(cur_total_eta)
-                ));
+                tpb.set_message(match limit {
+                    Limit::Iterations(n) => format!(
+                        "({:>5}/{:>5}) [{} / {}] ({})",
+                        current_total,
+                        n,
+                        FormattedDuration(cur_total_duration),
+                        total_formatted_duration,
+                        FormattedDuration(cur_total_eta)
+                    ),
+                    Limit::Duration { .. } => format!(
+                        "({:>5}) [{} / {}] ({})",
+                        current_total,
+                        FormattedDuration(cur_total_duration),
+                        total_formatted_duration,
+                        FormattedDuration(cur_total_eta)
+                    ),
+                    Limit::Forever => format!(
+                        "({:>5}) [{}]",
+                        current_total,
+                        FormattedDuration(cur_total_duration)
+                    ),
+                });
                 spb.set_message(format!(
                     "({:>5}/{:>5}) [{} / {}] ({})",
                     current_event,
@@ -217,12 +286,25 @@ impl Run for Play {
 
         tpb.finish_and_clear();
         spb.finish_and_clear();
-        mp.clear();
+        mp.clear().ok();
 
-        if !finished_successfull {
-            std::process::exit(1);
+        let summary = handle.wait()?.summary();
+        if !self.dry_run {
+            print!("{}", summary);
+            if let Some(path) = self.report {
+                let file = File::create(path)?;
+                serde_json::to_writer_pretty(file, &summary)?;
+            }
         }
 
+        if let Some(e) = listen_error {
+            return Err(e);
+        }
+
+        // Playback without a limit can only end by being stopped
+        if !finished_successfull && !matches!(limit, Limit::Forever) {
+            std::process::exit(1);
+        }
         Ok(())
     }
 }
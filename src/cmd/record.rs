@@ -1,122 +1,74 @@
-use std::{fs::File, io::Write, str::FromStr, sync::mpsc::channel, thread, time::SystemTime};
-
-use rdev::listen;
-
-use crate::{
-    cli::{Record, Run},
-    event::Event,
-    keys::{Key, KeyState},
+use mkrp::{
+    backend::{InputSink, RdevSink},
+    recorder::StopReason,
+    EventType, Key, Recorder, RecorderEvent,
 };
 
+use crate::cli::{Record, Run};
+
 impl Run for Record {
     fn run(self) -> eyre::Result<()> {
-        let stop_state = match self.stop_key {
-            Some(s) => {
-                let mut state = KeyState::default();
-                for item in s.split(',') {
-                    let key = Key::from_str(item)
-                        .ok_or(eyre::eyre!("Unknown key '{}' for stop key", item))?;
-                    state.set_pressed(key);
-                }
-                state
-            }
-            None => KeyState::with_pressed(&[Key::Escape]),
-        };
-
-        // spawn new thread because listen blocks
-        let (tx, rx) = channel();
-        let mut prev_system_time = SystemTime::now();
+        // The display server is queried independent of the backend. Without one the display and
+        // pointer position are unknown.
+        let sink = RdevSink::new();
 
-        let _listener = thread::spawn(move || {
-            listen(move |event| {
-                tx.send(event)
-                    .unwrap_or_else(|e| println!("Could not send event {:?}", e));
-            })
-            .expect("Could not listen");
-        });
+        // Resolution of the display so coordinates can be remapped when played on another display
+        let display = sink.display_size();
 
-        let mut keystate = KeyState::default();
-        let mut events = Vec::new();
-        for event in rx.iter() {
-            match event.event_type {
-                rdev::EventType::KeyPress(rkey) => {
-                    let key: Key = rkey.into();
-                    if !keystate.is_pressed(key) {
-                        // NOTE: Check if pressing this key satifies the stop state. This has to be
-                        // checked first because we dont want to record the event as iterations
-                        // would stop after the first iteration.
-                        keystate.set_pressed(key);
-                        if keystate.is_state_held(stop_state) {
-                            break;
-                        }
-
-                        let duration = event
-                            .time
-                            .duration_since(prev_system_time)
-                            .expect("failed to get duration since last event");
-
-                        events.push(Event {
-                            delay: duration,
-                            event: event.event_type,
-                        });
-                        prev_system_time = event.time;
-                        println!("{:?} Pressed, Duration: {:?}", key, duration);
-                    }
-                }
-                rdev::EventType::KeyRelease(rkey) => {
-                    let key: Key = rkey.into();
-                    if keystate.is_pressed(key) {
-                        let duration = event
-                            .time
-                            .duration_since(prev_system_time)
-                            .expect("failed to get duration since last event");
+        // Starting point for relative mouse motion. If the pointer position cannot be queried the
+        // first mouse move is used as the starting point instead.
+        let position = if self.relative_motion {
+            sink.position()
+        } else {
+            None
+        };
 
-                        events.push(Event {
-                            delay: duration,
-                            event: event.event_type,
-                        });
-                        keystate.set_released(key);
-                        prev_system_time = event.time;
-                        println!("{:?} Released, Duration: {:?}", key, duration);
-                    }
-                }
-                rdev::EventType::MouseMove { x, y } => {
-                    let duration = event
-                        .time
-                        .duration_since(prev_system_time)
-                        .expect("failed to get duration since last event");
-                    if duration.as_millis() >= 1 {
-                        events.push(Event {
-                            delay: duration,
-                            event: event.event_type,
-                        });
+        let mut recorder = Recorder::default()
+            .stop_key(super::parse_keys(self.stop_key.as_deref(), "stop key")?)
+            .relative_motion(self.relative_motion)
+            .display(display)
+            .position(position)
+            .on_event(print_event);
+        if let Some(duration) = self.max_duration {
+            recorder = recorder.max_duration(duration);
+        }
+        if let Some(count) = self.max_events {
+            recorder = recorder.max_events(count);
+        }
+        if let Some(timeout) = self.idle_timeout {
+            recorder = recorder.idle_timeout(timeout);
+        }
+        if let Some(s) = &self.marker_key {
+            let key = Key::from_str(s).ok_or(eyre::eyre!("Unknown key '{}' for marker key", s))?;
+            recorder = recorder.marker_key(key);
+        }
 
-                        prev_system_time = event.time;
-                        println!("Move ({},{}), Duration {:?}", x, y, duration);
-                    }
-                }
-                e => {
-                    let duration = event
-                        .time
-                        .duration_since(prev_system_time)
-                        .expect("failed to get duration since last event");
+        let source = self.backend.source(&self.device)?;
+        let session = recorder.start(source).wait()?;
+        session.to_file(&self.output)?;
 
-                    events.push(Event {
-                        delay: duration,
-                        event: event.event_type,
-                    });
+        Ok(())
+    }
+}
 
-                    prev_system_time = event.time;
-                    println!("Received {:?}, Duration {:?}", e, duration);
-                }
-            };
+fn print_event(event: &RecorderEvent) {
+    match event {
+        RecorderEvent::Recorded(e) => match e.event {
+            EventType::KeyPress(key) => println!("{:?} Pressed, Duration: {:?}", key, e.delay),
+            EventType::KeyRelease(key) => println!("{:?} Released, Duration: {:?}", key, e.delay),
+            EventType::MouseMove { x, y } => println!("Move ({},{}), Duration {:?}", x, y, e.delay),
+            event => println!("Received {}, Duration {:?}", event, e.delay),
+        },
+        RecorderEvent::Marker(marker) => println!("Marker {} placed", marker.name),
+        RecorderEvent::Stopped(StopReason::Duration) => {
+            println!("Recording stopped: duration limit reached")
         }
-
-        let mut file = File::create(self.output).unwrap();
-        for event in events {
-            writeln!(file, "{}", event).unwrap();
+        RecorderEvent::Stopped(StopReason::Idle) => {
+            println!("Recording stopped: idle timeout reached")
         }
-
-        Ok(())
+        RecorderEvent::Stopped(StopReason::EventLimit) => {
+            println!("Recording stopped: event limit reached")
+        }
+        RecorderEvent::Stopped(_) => {}
     }
 }
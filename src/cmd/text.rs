@@ -0,0 +1,16 @@
+use mkrp::{text::typed_text, Session};
+
+use crate::cli::{Run, Text};
+
+impl Run for Text {
+    fn run(self) -> eyre::Result<()> {
+        let session = Session::from_file(&self.input)?;
+        let text = typed_text(&session, self.layout);
+        if text.ends_with('\n') {
+            print!("{}", text);
+        } else {
+            println!("{}", text);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,25 @@
+use mkrp::{schedule::format_timestamp, Session};
+
+use crate::cli::{Run, Trim};
+
+impl Run for Trim {
+    fn run(self) -> eyre::Result<()> {
+        let mut session = Session::from_file(&self.input)?;
+        let (events, duration) = (session.events.len(), session.total_time);
+
+        session.trim();
+        if let Some(max) = self.max_delay {
+            session.cap_delay(max);
+        }
+        session.to_file(&self.output)?;
+
+        println!(
+            "Trimmed {} events and {} to {} events and {}",
+            events,
+            format_timestamp(duration),
+            session.events.len(),
+            format_timestamp(session.total_time)
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,39 @@
+use std::io::Read;
+
+use mkrp::{Player, SessionBuilder};
+
+use crate::cli::{Run, Type};
+
+impl Run for Type {
+    fn run(self) -> eyre::Result<()> {
+        let text = match (&self.text, &self.file) {
+            (Some(text), _) => text.clone(),
+            (None, Some(path)) => std::fs::read_to_string(path)?,
+            (None, None) => {
+                let mut text = String::new();
+                std::io::stdin().read_to_string(&mut text)?;
+                text
+            }
+        };
+
+        let mut builder = SessionBuilder::new().layout(self.layout).hold(self.hold);
+        for (i, c) in text.chars().enumerate() {
+            if i > 0 {
+                builder = builder.wait(self.delay);
+            }
+            builder = builder.type_char(c);
+        }
+        let session = builder.build()?;
+
+        let sink = self.backend.keyboard_sink()?;
+        let summary = Player::new(session).start(sink).wait()?.summary();
+        if summary.failures > 0 {
+            return Err(eyre::eyre!(
+                "Could not simulate {} key events",
+                summary.failures
+            ));
+        }
+
+        Ok(())
+    }
+}
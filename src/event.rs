@@ -1,52 +1,125 @@
 use std::{fmt::Display, time::Duration};
 
-use rdev::{listen, simulate, Button, Event as RdEvent, EventType};
-
 use crate::{keys::Key, mouse::MouseButton};
 
-#[derive(Debug, Clone)]
-pub enum RawEvent {
-    Terminate,
-    Event(RdEvent),
-}
-
 #[derive(Debug, Clone)]
 pub struct Event {
     pub delay: Duration,
     pub event: EventType,
+    /// Id of the device the event was recorded from, see `Session::devices`
+    pub device: Option<u16>,
+}
+
+/// Input event stored in a session.
+///
+/// This mirrors `rdev::EventType` with the addition of events that have no direct rdev
+/// counterpart, like relative mouse motion.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EventType {
+    KeyPress(Key),
+    KeyRelease(Key),
+    ButtonPress(MouseButton),
+    ButtonRelease(MouseButton),
+    MouseMove {
+        x: f64,
+        y: f64,
+    },
+    /// Mouse motion relative to the position of the pointer at the time the event is played.
+    MouseMoveRelative {
+        dx: f64,
+        dy: f64,
+    },
+    Wheel {
+        delta_x: i64,
+        delta_y: i64,
+    },
+}
+
+impl EventType {
+    /// Convert into an event that can be simulated by rdev.
+    ///
+    /// Relative mouse motion has to be resolved against the current pointer position before it can
+    /// be simulated and therefore returns `None`.
+    pub fn to_rdev(self) -> Option<rdev::EventType> {
+        Some(match self {
+            EventType::KeyPress(key) => rdev::EventType::KeyPress(key.into()),
+            EventType::KeyRelease(key) => rdev::EventType::KeyRelease(key.into()),
+            EventType::ButtonPress(button) => rdev::EventType::ButtonPress(button.into()),
+            EventType::ButtonRelease(button) => rdev::EventType::ButtonRelease(button.into()),
+            EventType::MouseMove { x, y } => rdev::EventType::MouseMove { x, y },
+            EventType::MouseMoveRelative { .. } => return None,
+            EventType::Wheel { delta_x, delta_y } => rdev::EventType::Wheel { delta_x, delta_y },
+        })
+    }
+}
+
+impl From<rdev::EventType> for EventType {
+    fn from(value: rdev::EventType) -> Self {
+        match value {
+            rdev::EventType::KeyPress(key) => EventType::KeyPress(key.into()),
+            rdev::EventType::KeyRelease(key) => EventType::KeyRelease(key.into()),
+            rdev::EventType::ButtonPress(button) => EventType::ButtonPress(button.into()),
+            rdev::EventType::ButtonRelease(button) => EventType::ButtonRelease(button.into()),
+            rdev::EventType::MouseMove { x, y } => EventType::MouseMove { x, y },
+            rdev::EventType::Wheel { delta_x, delta_y } => EventType::Wheel { delta_x, delta_y },
+        }
+    }
+}
+
+impl Display for EventType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match *self {
+            EventType::KeyPress(key) => write!(f, "key press {}", key.to_str()),
+            EventType::KeyRelease(key) => write!(f, "key release {}", key.to_str()),
+            EventType::ButtonPress(button) => write!(f, "button press {}", button.to_str()),
+            EventType::ButtonRelease(button) => {
+                write!(f, "button release {}", button.to_str())
+            }
+            EventType::MouseMove { x, y } => write!(f, "mouse move to {},{}", x, y),
+            EventType::MouseMoveRelative { dx, dy } => write!(f, "mouse move by {},{}", dx, dy),
+            EventType::Wheel { delta_x, delta_y } => {
+                write!(f, "wheel {},{}", delta_x, delta_y)
+            }
+        }
+    }
 }
 
 impl Display for Event {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self.event {
             EventType::KeyPress(key) => {
-                write!(f, "{},kp,{}", self.delay.as_millis(), Key::from(key) as u8)
+                write!(f, "{},kp,{}", self.delay.as_millis(), key as u8)
             }
             EventType::KeyRelease(key) => {
-                write!(f, "{},kr,{}", self.delay.as_millis(), Key::from(key) as u8)
+                write!(f, "{},kr,{}", self.delay.as_millis(), key as u8)
             }
             EventType::ButtonPress(button) => {
-                write!(
-                    f,
-                    "{},mp,{}",
-                    self.delay.as_millis(),
-                    MouseButton::from(button) as u8
-                )
+                write!(f, "{},mp,{}", self.delay.as_millis(), button as u8)
             }
             EventType::ButtonRelease(button) => {
+                write!(f, "{},mr,{}", self.delay.as_millis(), button as u8)
+            }
+            EventType::MouseMove { x, y } => {
+                write!(f, "{},mm,{},{}", self.delay.as_millis(), x as i64, y as i64)
+            }
+            EventType::MouseMoveRelative { dx, dy } => {
                 write!(
                     f,
-                    "{},mr,{}",
+                    "{},md,{},{}",
                     self.delay.as_millis(),
-                    MouseButton::from(button) as u8
+                    dx as i64,
+                    dy as i64
                 )
             }
-            EventType::MouseMove { x, y } => {
-                write!(f, "{},mm,{},{}", self.delay.as_millis(), x as i64, y as i64)
-            }
             EventType::Wheel { delta_x, delta_y } => {
                 write!(f, "{},mw,{},{}", self.delay.as_millis(), delta_x, delta_y)
             }
+        }?;
+
+        // The device is stored as an optional last column
+        match self.device {
+            Some(device) => write!(f, ",{}", device),
+            None => Ok(()),
         }
     }
 }
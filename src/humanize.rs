@@ -0,0 +1,225 @@
+use std::{str::FromStr, time::Duration};
+
+use clap::ValueEnum;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use rand_distr::{Distribution as _, Normal};
+
+use crate::{
+    event::{Event, EventType},
+    mouse::MouseButton,
+    schedule::parse_duration,
+    session::Session,
+};
+
+/// Amount of random variation applied to the delay of an event.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Jitter {
+    /// Percentage of the delay of each event
+    Percent(f64),
+    /// Fixed amount independent of the delay of the event
+    Absolute(Duration),
+}
+
+impl FromStr for Jitter {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.strip_suffix('%') {
+            Some(percent) => percent
+                .trim()
+                .parse::<f64>()
+                .ok()
+                .filter(|p| p.is_finite() && *p >= 0.0)
+                .map(Jitter::Percent)
+                .ok_or_else(|| format!("invalid percentage '{}'", s)),
+            None => parse_duration(s).map(Jitter::Absolute),
+        }
+    }
+}
+
+/// Distribution the random variation is drawn from.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Distribution {
+    /// Evenly distributed between minus and plus the jitter amount
+    #[default]
+    Uniform,
+    /// Normal distribution using the jitter amount as standard deviation
+    Normal,
+}
+
+/// Randomly perturbs the timing and click positions of a session.
+///
+/// Delays are only ever changed and never dropped below zero, so the order of events is kept as is
+/// and a key is never released before it is pressed.
+pub struct Humanizer {
+    rng: StdRng,
+    jitter: Option<Jitter>,
+    distribution: Distribution,
+    click_jitter: Option<f64>,
+}
+
+impl Humanizer {
+    pub fn new(
+        seed: u64,
+        jitter: Option<Jitter>,
+        distribution: Distribution,
+        click_jitter: Option<f64>,
+    ) -> Self {
+        assert!(
+            click_jitter.is_none_or(|pixels| pixels.is_finite() && pixels >= 0.0),
+            "click jitter must be zero or a positive number"
+        );
+        Self {
+            rng: StdRng::seed_from_u64(seed),
+            jitter,
+            distribution,
+            click_jitter,
+        }
+    }
+
+    /// Create a perturbed copy of the session.
+    pub fn apply(&mut self, session: &Session) -> Session {
+        let mut events = session.events.clone();
+
+        if let Some(jitter) = self.jitter {
+            for event in &mut events {
+                event.delay = self.perturb(event.delay, jitter);
+            }
+        }
+
+        if let Some(pixels) = self.click_jitter {
+            self.offset_clicks(&mut events, pixels);
+        }
+
+        let mut humanized = Session::new(events, session.display);
+        humanized.markers = session.markers.clone();
+        humanized.devices = session.devices.clone();
+        humanized
+    }
+
+    fn perturb(&mut self, delay: Duration, jitter: Jitter) -> Duration {
+        let amount = match jitter {
+            Jitter::Percent(p) => delay.as_secs_f64() * p / 100.0,
+            Jitter::Absolute(d) => d.as_secs_f64(),
+        };
+        let offset = self.sample(amount);
+        Duration::from_secs_f64((delay.as_secs_f64() + offset).max(0.0))
+    }
+
+    fn sample(&mut self, amount: f64) -> f64 {
+        if amount <= 0.0 {
+            return 0.0;
+        }
+
+        match self.distribution {
+            Distribution::Uniform => self.rng.gen_range(-amount..=amount),
+            Distribution::Normal => Normal::new(0.0, amount)
+                .expect("standard deviation is positive")
+                .sample(&mut self.rng),
+        }
+    }
+
+    /// Move the position of every click by a random offset.
+    ///
+    /// The offset is applied to the last mouse move before the button is pressed and kept for all
+    /// moves until the button is released so that drags keep their shape.
+    fn offset_clicks(&mut self, events: &mut [Event], pixels: f64) {
+        let mut last_move = None;
+        let mut held: Option<(MouseButton, f64, f64)> = None;
+
+        for i in 0..events.len() {
+            match events[i].event {
+                EventType::MouseMove { x, y } => match held {
+                    Some((_, dx, dy)) => {
+                        events[i].event = EventType::MouseMove {
+                            x: x + dx,
+                            y: y + dy,
+                        }
+                    }
+                    None => last_move = Some(i),
+                },
+                EventType::ButtonPress(button) if held.is_none() => {
+                    let dx = self.rng.gen_range(-pixels..=pixels).round();
+                    let dy = self.rng.gen_range(-pixels..=pixels).round();
+                    if let Some(index) = last_move {
+                        if let EventType::MouseMove { x, y } = events[index].event {
+                            events[index].event = EventType::MouseMove {
+                                x: x + dx,
+                                y: y + dy,
+                            };
+                        }
+                    }
+                    held = Some((button, dx, dy));
+                }
+                EventType::ButtonRelease(button) if held.is_some_and(|(b, ..)| b == button) => {
+                    held = None;
+                    last_move = None;
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_jitter() {
+        assert_eq!("10%".parse(), Ok(Jitter::Percent(10.0)));
+        assert_eq!(
+            "15ms".parse(),
+            Ok(Jitter::Absolute(Duration::from_millis(15)))
+        );
+        assert!("-5%".parse::<Jitter>().is_err());
+        assert!("abc".parse::<Jitter>().is_err());
+    }
+
+    #[test]
+    #[should_panic(expected = "click jitter")]
+    fn reject_negative_click_jitter() {
+        Humanizer::new(0, None, Distribution::Uniform, Some(-3.0));
+    }
+
+    #[test]
+    fn jitter_is_reproducible_and_keeps_order() {
+        let session =
+            Session::from_str("0,mm,100,100\n200,mp,1\n10,mm,120,100\n50,mr,1\n100,kp,1\n5,kr,1")
+                .unwrap();
+        let humanize = || {
+            Humanizer::new(
+                42,
+                Some(Jitter::Absolute(Duration::from_millis(50))),
+                Distribution::Normal,
+                Some(3.0),
+            )
+            .apply(&session)
+        };
+
+        let first = humanize();
+        let second = humanize();
+        for (a, b) in first.events.iter().zip(&second.events) {
+            assert_eq!(a.delay, b.delay);
+            assert_eq!(a.event, b.event);
+        }
+
+        // Event order is unchanged
+        for (a, b) in first.events.iter().zip(&session.events) {
+            assert_eq!(
+                std::mem::discriminant(&a.event),
+                std::mem::discriminant(&b.event)
+            );
+        }
+
+        // The drag keeps its shape
+        match (first.events[0].event, first.events[2].event) {
+            (EventType::MouseMove { x: x1, y: y1 }, EventType::MouseMove { x: x2, y: y2 }) => {
+                assert_eq!(x2 - x1, 20.0);
+                assert_eq!(y2 - y1, 0.0);
+                assert!((x1 - 100.0).abs() <= 3.0 && (y1 - 100.0).abs() <= 3.0);
+            }
+            _ => unreachable!(),
+        }
+    }
+}
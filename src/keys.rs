@@ -1,5 +1,4 @@
 use phf::phf_map;
-use std::{error::Error, str::FromStr, string::ParseError};
 
 #[derive(Debug, Default, Clone, Copy)]
 pub struct KeyState(u128);
@@ -131,6 +130,11 @@ pub enum Key {
     PrintScreen,
 
     Unknown,
+
+    // NOTE: Keys added after `Unknown` so that the value of `Unknown` stays the same in existing
+    // sessions.
+    /// Additional key next to the left shift key on ISO keyboards
+    IntlBackslash,
 }
 
 static STR_TO_KEYS: phf::Map<&'static str, Key> = phf_map! {
@@ -223,9 +227,11 @@ static STR_TO_KEYS: phf::Map<&'static str, Key> = phf_map! {
     "insert" => Key::Insert ,
     "printscreen" => Key::PrintScreen ,
     "unknown" => Key::Unknown ,
+    "intlbackslash" => Key::IntlBackslash ,
 };
 
 impl Key {
+    #[allow(clippy::should_implement_trait)]
     pub fn from_str(s: &str) -> Option<Key> {
         STR_TO_KEYS.get(s).cloned()
     }
@@ -317,21 +323,20 @@ impl Key {
             Key::Insert => "insert",
             Key::PrintScreen => "printscreen",
             Key::Unknown => "unknown",
+            Key::IntlBackslash => "intlbackslash",
         }
     }
 }
 
 impl From<u8> for Key {
     fn from(value: u8) -> Self {
-        if value == 0 || value >= Key::Unknown as u8 {
+        if value == 0 || value > Key::IntlBackslash as u8 {
             return Key::Unknown;
         }
 
-        /// SAFTY: The bounds of the value have been checked above, the rest of the values are
-        /// valid keys.
-        unsafe {
-            std::mem::transmute(value)
-        }
+        // SAFTY: The bounds of the value have been checked above, the rest of the values are
+        // valid keys.
+        unsafe { std::mem::transmute(value) }
     }
 }
 
@@ -367,7 +372,6 @@ impl From<Key> for rdev::Key {
             Key::F9 => rdev::Key::F9,
             Key::Home => rdev::Key::Home,
             Key::Left => rdev::Key::LeftArrow,
-            Key::Down => rdev::Key::PageDown,
             Key::Up => rdev::Key::UpArrow,
             Key::Return => rdev::Key::Return,
             Key::Right => rdev::Key::RightArrow,
@@ -376,7 +380,6 @@ impl From<Key> for rdev::Key {
             Key::Space => rdev::Key::Space,
             Key::Tab => rdev::Key::Tab,
             Key::Capslock => rdev::Key::CapsLock,
-            Key::Up => rdev::Key::UpArrow,
             Key::PrintScreen => rdev::Key::PrintScreen,
             Key::Backquote => rdev::Key::BackQuote,
             Key::Num0 => rdev::Key::Num0,
@@ -427,6 +430,7 @@ impl From<Key> for rdev::Key {
             Key::Slash => rdev::Key::Slash,
             Key::Insert => rdev::Key::Insert,
             Key::Unknown => rdev::Key::Unknown(0),
+            Key::IntlBackslash => rdev::Key::IntlBackslash,
             Key::LSuper => rdev::Key::MetaLeft,
             Key::RSuper => rdev::Key::MetaRight,
             Key::Pageup => rdev::Key::PageUp,
@@ -464,8 +468,8 @@ impl From<rdev::Key> for Key {
             rdev::Key::LeftArrow => Key::Left,
             rdev::Key::MetaLeft => Key::LSuper,
             rdev::Key::MetaRight => Key::RSuper,
-            rdev::Key::PageDown => Key::Down,
-            rdev::Key::PageUp => Key::Up,
+            rdev::Key::PageDown => Key::Pagedown,
+            rdev::Key::PageUp => Key::Pageup,
             rdev::Key::Return => Key::Return,
             rdev::Key::RightArrow => Key::Right,
             rdev::Key::ShiftLeft => Key::LShift,
@@ -474,9 +478,6 @@ impl From<rdev::Key> for Key {
             rdev::Key::Tab => Key::Tab,
             rdev::Key::UpArrow => Key::Up,
             rdev::Key::PrintScreen => Key::PrintScreen,
-            rdev::Key::ScrollLock => todo!(),
-            rdev::Key::Pause => todo!(),
-            rdev::Key::NumLock => todo!(),
             rdev::Key::BackQuote => Key::Backquote,
             rdev::Key::Num0 => Key::Num0,
             rdev::Key::Num1 => Key::Num1,
@@ -514,7 +515,7 @@ impl From<rdev::Key> for Key {
             rdev::Key::SemiColon => Key::SemiColon,
             rdev::Key::Quote => Key::Quote,
             rdev::Key::BackSlash => Key::Backslash,
-            rdev::Key::IntlBackslash => todo!(),
+            rdev::Key::IntlBackslash => Key::IntlBackslash,
             rdev::Key::KeyZ => Key::Z,
             rdev::Key::KeyX => Key::X,
             rdev::Key::KeyC => Key::C,
@@ -526,24 +527,9 @@ impl From<rdev::Key> for Key {
             rdev::Key::Dot => Key::Dot,
             rdev::Key::Slash => Key::Slash,
             rdev::Key::Insert => Key::Insert,
-            rdev::Key::KpReturn => todo!(),
-            rdev::Key::KpMinus => todo!(),
-            rdev::Key::KpPlus => todo!(),
-            rdev::Key::KpMultiply => todo!(),
-            rdev::Key::KpDivide => todo!(),
-            rdev::Key::Kp0 => todo!(),
-            rdev::Key::Kp1 => todo!(),
-            rdev::Key::Kp2 => todo!(),
-            rdev::Key::Kp3 => todo!(),
-            rdev::Key::Kp4 => todo!(),
-            rdev::Key::Kp5 => todo!(),
-            rdev::Key::Kp6 => todo!(),
-            rdev::Key::Kp7 => todo!(),
-            rdev::Key::Kp8 => todo!(),
-            rdev::Key::Kp9 => todo!(),
-            rdev::Key::KpDelete => todo!(),
-            rdev::Key::Unknown(_) => Key::Unknown,
-            _ => todo!(),
+            // Keys without a counterpart like the numpad are kept as unknown so they can still
+            // be pressed while recording or playing without stopping either
+            _ => Key::Unknown,
         }
     }
 }
@@ -592,13 +578,23 @@ mod tests {
         assert_eq!(Key::from(200u8), Key::Unknown);
     }
 
+    #[test]
+    fn convert_to_and_from_rdev() {
+        for i in 1..=Key::IntlBackslash as u8 {
+            let key = Key::from(i);
+            assert_eq!(Key::from(rdev::Key::from(key)), key);
+        }
+        assert_eq!(Key::from(rdev::Key::Kp5), Key::Unknown);
+        assert_eq!(Key::from(rdev::Key::NumLock), Key::Unknown);
+    }
+
     #[test]
     fn convert_to_and_from_str() {
-        let last_key_value = Key::Unknown as u8;
+        let last_key_value = Key::IntlBackslash as u8;
         for i in 1..=last_key_value {
             let key = Key::from(i);
 
-            if i < last_key_value {
+            if i != Key::Unknown as u8 {
                 assert_ne!(key, Key::Unknown);
             }
 
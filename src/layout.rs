@@ -0,0 +1,402 @@
+use std::fmt;
+
+use clap::ValueEnum;
+
+use crate::keys::Key;
+
+/// A key together with the modifiers that have to be held while pressing it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Stroke {
+    pub key: Key,
+    pub shift: bool,
+    /// Right alt, used as the AltGr key on most non US layouts
+    pub altgr: bool,
+}
+
+const fn plain(key: Key) -> Stroke {
+    Stroke {
+        key,
+        shift: false,
+        altgr: false,
+    }
+}
+
+const fn shift(key: Key) -> Stroke {
+    Stroke {
+        key,
+        shift: true,
+        altgr: false,
+    }
+}
+
+const fn altgr(key: Key) -> Stroke {
+    Stroke {
+        key,
+        shift: false,
+        altgr: true,
+    }
+}
+
+/// Keyboard layout used to convert between text and key strokes.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Layout {
+    /// US English (ANSI)
+    #[default]
+    Us,
+    /// UK English (ISO)
+    Uk,
+    /// German QWERTZ (ISO) with dead keys for accents
+    De,
+}
+
+impl fmt::Display for Layout {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Layout::Us => "us",
+            Layout::Uk => "uk",
+            Layout::De => "de",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+const US: &[(char, Stroke)] = &[
+    (')', shift(Key::Num0)),
+    ('!', shift(Key::Num1)),
+    ('@', shift(Key::Num2)),
+    ('#', shift(Key::Num3)),
+    ('$', shift(Key::Num4)),
+    ('%', shift(Key::Num5)),
+    ('^', shift(Key::Num6)),
+    ('&', shift(Key::Num7)),
+    ('*', shift(Key::Num8)),
+    ('(', shift(Key::Num9)),
+    (';', plain(Key::SemiColon)),
+    (':', shift(Key::SemiColon)),
+    (',', plain(Key::Comma)),
+    ('<', shift(Key::Comma)),
+    ('.', plain(Key::Dot)),
+    ('>', shift(Key::Dot)),
+    ('/', plain(Key::Slash)),
+    ('?', shift(Key::Slash)),
+    ('\\', plain(Key::Backslash)),
+    ('|', shift(Key::Backslash)),
+    ('[', plain(Key::LBracket)),
+    ('{', shift(Key::LBracket)),
+    (']', plain(Key::RBracket)),
+    ('}', shift(Key::RBracket)),
+    ('\'', plain(Key::Quote)),
+    ('"', shift(Key::Quote)),
+    ('`', plain(Key::Backquote)),
+    ('~', shift(Key::Backquote)),
+    ('-', plain(Key::Minus)),
+    ('_', shift(Key::Minus)),
+    ('=', plain(Key::Equal)),
+    ('+', shift(Key::Equal)),
+];
+
+// The `Backslash` key is the one next to return, which is labelled `#` on ISO keyboards.
+const UK: &[(char, Stroke)] = &[
+    (')', shift(Key::Num0)),
+    ('!', shift(Key::Num1)),
+    ('"', shift(Key::Num2)),
+    ('£', shift(Key::Num3)),
+    ('$', shift(Key::Num4)),
+    ('€', altgr(Key::Num4)),
+    ('%', shift(Key::Num5)),
+    ('^', shift(Key::Num6)),
+    ('&', shift(Key::Num7)),
+    ('*', shift(Key::Num8)),
+    ('(', shift(Key::Num9)),
+    (';', plain(Key::SemiColon)),
+    (':', shift(Key::SemiColon)),
+    (',', plain(Key::Comma)),
+    ('<', shift(Key::Comma)),
+    ('.', plain(Key::Dot)),
+    ('>', shift(Key::Dot)),
+    ('/', plain(Key::Slash)),
+    ('?', shift(Key::Slash)),
+    ('#', plain(Key::Backslash)),
+    ('~', shift(Key::Backslash)),
+    ('\\', plain(Key::IntlBackslash)),
+    ('|', shift(Key::IntlBackslash)),
+    ('[', plain(Key::LBracket)),
+    ('{', shift(Key::LBracket)),
+    (']', plain(Key::RBracket)),
+    ('}', shift(Key::RBracket)),
+    ('\'', plain(Key::Quote)),
+    ('@', shift(Key::Quote)),
+    ('`', plain(Key::Backquote)),
+    ('¬', shift(Key::Backquote)),
+    ('¦', altgr(Key::Backquote)),
+    ('-', plain(Key::Minus)),
+    ('_', shift(Key::Minus)),
+    ('=', plain(Key::Equal)),
+    ('+', shift(Key::Equal)),
+];
+
+const DE: &[(char, Stroke)] = &[
+    ('=', shift(Key::Num0)),
+    ('}', altgr(Key::Num0)),
+    ('!', shift(Key::Num1)),
+    ('"', shift(Key::Num2)),
+    ('²', altgr(Key::Num2)),
+    ('§', shift(Key::Num3)),
+    ('³', altgr(Key::Num3)),
+    ('$', shift(Key::Num4)),
+    ('%', shift(Key::Num5)),
+    ('&', shift(Key::Num6)),
+    ('/', shift(Key::Num7)),
+    ('{', altgr(Key::Num7)),
+    ('(', shift(Key::Num8)),
+    ('[', altgr(Key::Num8)),
+    (')', shift(Key::Num9)),
+    (']', altgr(Key::Num9)),
+    ('ß', plain(Key::Minus)),
+    ('?', shift(Key::Minus)),
+    ('\\', altgr(Key::Minus)),
+    ('°', shift(Key::Backquote)),
+    ('ü', plain(Key::LBracket)),
+    ('Ü', shift(Key::LBracket)),
+    ('+', plain(Key::RBracket)),
+    ('*', shift(Key::RBracket)),
+    ('~', altgr(Key::RBracket)),
+    ('ö', plain(Key::SemiColon)),
+    ('Ö', shift(Key::SemiColon)),
+    ('ä', plain(Key::Quote)),
+    ('Ä', shift(Key::Quote)),
+    ('#', plain(Key::Backslash)),
+    ('\'', shift(Key::Backslash)),
+    ('<', plain(Key::IntlBackslash)),
+    ('>', shift(Key::IntlBackslash)),
+    ('|', altgr(Key::IntlBackslash)),
+    (',', plain(Key::Comma)),
+    (';', shift(Key::Comma)),
+    ('.', plain(Key::Dot)),
+    (':', shift(Key::Dot)),
+    ('-', plain(Key::Slash)),
+    ('_', shift(Key::Slash)),
+    ('@', altgr(Key::Q)),
+    ('€', altgr(Key::E)),
+    ('µ', altgr(Key::M)),
+];
+
+/// Dead keys do not type anything on their own but put an accent on the next character.
+const DE_DEAD: &[(char, Stroke)] = &[
+    ('^', plain(Key::Backquote)),
+    ('´', plain(Key::Equal)),
+    ('`', shift(Key::Equal)),
+];
+
+/// Characters created by a dead key followed by a base character: accent, bases and results.
+const COMPOSE: &[(char, &str, &str)] = &[
+    ('^', "aeiouAEIOU", "âêîôûÂÊÎÔÛ"),
+    ('´', "aeiouyAEIOUY", "áéíóúýÁÉÍÓÚÝ"),
+    ('`', "aeiouAEIOU", "àèìòùÀÈÌÒÙ"),
+];
+
+impl Layout {
+    fn table(self) -> &'static [(char, Stroke)] {
+        match self {
+            Layout::Us => US,
+            Layout::Uk => UK,
+            Layout::De => DE,
+        }
+    }
+
+    fn dead_keys(self) -> &'static [(char, Stroke)] {
+        match self {
+            Layout::Us | Layout::Uk => &[],
+            Layout::De => DE_DEAD,
+        }
+    }
+
+    /// Key of a letter, `Y` and `Z` are swapped on the German layout.
+    fn letter(self, key: Key) -> Key {
+        match (self, key) {
+            (Layout::De, Key::Y) => Key::Z,
+            (Layout::De, Key::Z) => Key::Y,
+            _ => key,
+        }
+    }
+
+    fn dead_key(self, accent: char) -> Option<Stroke> {
+        self.dead_keys()
+            .iter()
+            .find(|(c, _)| *c == accent)
+            .map(|(_, stroke)| *stroke)
+    }
+
+    /// The single stroke that types `c`.
+    fn stroke(self, c: char) -> Option<Stroke> {
+        let stroke = match c {
+            'a'..='z' => plain(self.letter(Key::from(c as u8 - b'a' + Key::A as u8))),
+            'A'..='Z' => shift(self.letter(Key::from(c as u8 - b'A' + Key::A as u8))),
+            '0'..='9' => plain(Key::from(c as u8 - b'0' + Key::Num0 as u8)),
+            ' ' => plain(Key::Space),
+            '\n' => plain(Key::Return),
+            '\t' => plain(Key::Tab),
+            _ => {
+                return self
+                    .table()
+                    .iter()
+                    .find(|(t, _)| *t == c)
+                    .map(|(_, stroke)| *stroke)
+            }
+        };
+        Some(stroke)
+    }
+
+    /// Strokes that type `c` on this layout, or `None` if the layout cannot type it.
+    ///
+    /// Accented characters without a key of their own are typed with a dead key followed by the
+    /// base character. The accent on its own is typed with the dead key followed by a space.
+    pub fn strokes(self, c: char) -> Option<Vec<Stroke>> {
+        if let Some(stroke) = self.stroke(c) {
+            return Some(vec![stroke]);
+        }
+        if let Some(dead) = self.dead_key(c) {
+            return Some(vec![dead, plain(Key::Space)]);
+        }
+
+        COMPOSE.iter().find_map(|(accent, bases, results)| {
+            let base = bases.chars().zip(results.chars()).find(|(_, r)| *r == c)?.0;
+            Some(vec![self.dead_key(*accent)?, self.stroke(base)?])
+        })
+    }
+
+    /// Accent put on the next character if `stroke` is a dead key.
+    pub fn accent_of(self, stroke: Stroke) -> Option<char> {
+        self.dead_keys()
+            .iter()
+            .find(|(_, s)| *s == stroke)
+            .map(|(c, _)| *c)
+    }
+
+    /// Character typed by a single stroke, ignoring dead keys.
+    pub fn char_of(self, stroke: Stroke) -> Option<char> {
+        if let Some((c, _)) = self.table().iter().find(|(_, s)| *s == stroke) {
+            return Some(*c);
+        }
+        if stroke.altgr {
+            return None;
+        }
+
+        let value = stroke.key as u8;
+        let c = match stroke.key {
+            Key::Space => ' ',
+            Key::Return => '\n',
+            Key::Tab => '\t',
+            key if (Key::Num0..=Key::Num9).contains(&key) && !stroke.shift => {
+                (value - Key::Num0 as u8 + b'0') as char
+            }
+            key if (Key::A..=Key::Z).contains(&key) => {
+                let letter = self.letter(stroke.key) as u8 - Key::A as u8;
+                match stroke.shift {
+                    true => (letter + b'A') as char,
+                    false => (letter + b'a') as char,
+                }
+            }
+            _ => return None,
+        };
+        Some(c)
+    }
+
+    /// Text typed by a sequence of strokes. Strokes that do not type anything are skipped.
+    pub fn decode(self, strokes: &[Stroke]) -> String {
+        let mut text = String::new();
+        let mut dead_key = DeadKey::default();
+        for stroke in strokes {
+            if let Some(accent) = self.accent_of(*stroke) {
+                text.extend(dead_key.press(accent));
+            } else if let Some(c) = self.char_of(*stroke) {
+                text.extend(dead_key.type_char(c));
+            }
+        }
+        text.extend(dead_key.take());
+        text
+    }
+}
+
+/// Accent of a dead key that is waiting for the next character.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct DeadKey {
+    accent: Option<char>,
+}
+
+impl DeadKey {
+    /// Press the dead key of `accent`. A pending accent is typed on its own and returned.
+    pub fn press(&mut self, accent: char) -> Option<char> {
+        self.accent.replace(accent)
+    }
+
+    /// Type `c`, combining it with the pending accent. Returns the characters that are typed.
+    pub fn type_char(&mut self, c: char) -> impl Iterator<Item = char> {
+        let typed = match self.accent.take() {
+            Some(accent) if c == ' ' => [Some(accent), None],
+            Some(accent) => match compose(accent, c) {
+                Some(composed) => [Some(composed), None],
+                None => [Some(accent), Some(c)],
+            },
+            None => [Some(c), None],
+        };
+        typed.into_iter().flatten()
+    }
+
+    /// Remove the pending accent without typing anything.
+    pub fn take(&mut self) -> Option<char> {
+        self.accent.take()
+    }
+}
+
+/// Character created by typing the dead key of `accent` followed by `base`.
+pub fn compose(accent: char, base: char) -> Option<char> {
+    let (_, bases, results) = COMPOSE.iter().find(|(a, _, _)| *a == accent)?;
+    let index = bases.chars().position(|b| b == base)?;
+    results.chars().nth(index)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(layout: Layout, text: &str) {
+        let mut strokes = Vec::new();
+        for c in text.chars() {
+            let typed = layout
+                .strokes(c)
+                .unwrap_or_else(|| panic!("{:?} cannot type {:?}", layout, c));
+            assert_eq!(layout.decode(&typed), c.to_string(), "{:?}", layout);
+            strokes.extend(typed);
+        }
+        assert_eq!(layout.decode(&strokes), text, "{:?}", layout);
+    }
+
+    #[test]
+    fn round_trip_printable_ascii() {
+        let ascii: String = (' '..='~').chain(['\n', '\t']).collect();
+        for layout in Layout::value_variants() {
+            round_trip(*layout, &ascii);
+        }
+    }
+
+    #[test]
+    fn round_trip_latin1() {
+        round_trip(Layout::Uk, "£¬¦€");
+        round_trip(Layout::De, "äöüÄÖÜß§°²³µ€´âéèÁÛý");
+        assert_eq!(Layout::Us.strokes('é'), None);
+        assert_eq!(Layout::Uk.strokes('ä'), None);
+    }
+
+    #[test]
+    fn layout_specific_keys() {
+        assert_eq!(Layout::De.strokes('z'), Some(vec![plain(Key::Y)]));
+        assert_eq!(Layout::De.strokes('@'), Some(vec![altgr(Key::Q)]));
+        assert_eq!(Layout::Uk.strokes('@'), Some(vec![shift(Key::Quote)]));
+        assert_eq!(
+            Layout::De.strokes('ê'),
+            Some(vec![plain(Key::Backquote), plain(Key::E)])
+        );
+        assert_eq!(Layout::De.decode(&[plain(Key::Equal), plain(Key::X)]), "´x");
+    }
+}
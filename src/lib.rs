@@ -0,0 +1,29 @@
+//! Record and play back mouse and keyboard input.
+//!
+//! A [`Recorder`] listens to an input source and collects the events into a [`Session`], which
+//! can be saved to and loaded from a file. Sessions can also be created from code with a
+//! [`SessionBuilder`]. A [`Player`] plays a session back on an input sink.
+//! Input sources and sinks are provided by the [`backend`] module.
+
+pub mod backend;
+pub mod builder;
+pub mod event;
+pub mod humanize;
+pub mod keys;
+pub mod layout;
+pub mod lint;
+pub mod mouse;
+pub mod player;
+pub mod recorder;
+pub mod report;
+pub mod schedule;
+pub mod session;
+pub mod stats;
+pub mod text;
+
+pub use builder::SessionBuilder;
+pub use event::{Event, EventType};
+pub use keys::{Key, KeyState};
+pub use player::{Player, PlayerEvent, PlayerHandle};
+pub use recorder::{Recorder, RecorderEvent, RecorderHandle};
+pub use session::Session;
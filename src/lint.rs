@@ -0,0 +1,405 @@
+use std::{collections::HashMap, fmt::Display, time::Duration};
+
+use crate::{
+    event::{Event, EventType},
+    keys::{Key, KeyState},
+    mouse::{MouseButton, MouseState},
+    session::{Marker, Session},
+};
+
+/// Structural problem found in a session.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Problem {
+    /// A key is released without being pressed
+    UnmatchedKeyRelease(Key),
+    UnmatchedButtonRelease(MouseButton),
+    /// A key is pressed and never released
+    KeyNeverReleased(Key),
+    ButtonNeverReleased(MouseButton),
+    /// A key is pressed while it is already held down
+    DuplicateKeyPress(Key),
+    DuplicateButtonPress(MouseButton),
+    /// The mouse is moved outside of the display the session was recorded on
+    OutsideDisplay {
+        x: f64,
+        y: f64,
+        display: (u64, u64),
+    },
+    /// A key event with a key that could not be identified when recording
+    UnknownKey,
+    /// The session does not contain any events
+    Empty,
+    /// The session contains events but takes no time to play
+    ZeroDuration,
+}
+
+impl Display for Problem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match *self {
+            Problem::UnmatchedKeyRelease(key) => {
+                write!(f, "key {} is released without being pressed", key.to_str())
+            }
+            Problem::UnmatchedButtonRelease(button) => write!(
+                f,
+                "button {} is released without being pressed",
+                button.to_str()
+            ),
+            Problem::KeyNeverReleased(key) => {
+                write!(f, "key {} is pressed and never released", key.to_str())
+            }
+            Problem::ButtonNeverReleased(button) => {
+                write!(
+                    f,
+                    "button {} is pressed and never released",
+                    button.to_str()
+                )
+            }
+            Problem::DuplicateKeyPress(key) => {
+                write!(f, "key {} is pressed while already held", key.to_str())
+            }
+            Problem::DuplicateButtonPress(button) => write!(
+                f,
+                "button {} is pressed while already held",
+                button.to_str()
+            ),
+            Problem::OutsideDisplay { x, y, display } => write!(
+                f,
+                "mouse moves to {},{} outside of the {}x{} display",
+                x, y, display.0, display.1
+            ),
+            Problem::UnknownKey => write!(f, "key event with an unknown key"),
+            Problem::Empty => write!(f, "session does not contain any events"),
+            Problem::ZeroDuration => write!(f, "session has a duration of zero"),
+        }
+    }
+}
+
+/// A problem together with the index of the event it was found at, if it belongs to one.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Diagnostic {
+    pub index: Option<usize>,
+    pub problem: Problem,
+}
+
+/// Find structural problems in a session. Diagnostics are ordered by event index, problems of
+/// the whole session come last.
+pub fn check(session: &Session) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    let mut report = |index, problem| {
+        diagnostics.push(Diagnostic {
+            index: Some(index),
+            problem,
+        })
+    };
+
+    // Index of the press of everything currently held down
+    let mut keys = HashMap::new();
+    let mut buttons = HashMap::new();
+    for (i, event) in session.events.iter().enumerate() {
+        match event.event {
+            EventType::KeyPress(key) | EventType::KeyRelease(key) if key == Key::Unknown => {
+                report(i, Problem::UnknownKey)
+            }
+            EventType::KeyPress(key) => {
+                if keys.insert(key, i).is_some() {
+                    report(i, Problem::DuplicateKeyPress(key));
+                }
+            }
+            EventType::KeyRelease(key) => {
+                if keys.remove(&key).is_none() {
+                    report(i, Problem::UnmatchedKeyRelease(key));
+                }
+            }
+            EventType::ButtonPress(button) => {
+                if buttons.insert(button, i).is_some() {
+                    report(i, Problem::DuplicateButtonPress(button));
+                }
+            }
+            EventType::ButtonRelease(button) => {
+                if buttons.remove(&button).is_none() {
+                    report(i, Problem::UnmatchedButtonRelease(button));
+                }
+            }
+            EventType::MouseMove { x, y } => {
+                if let Some(display) = session.display {
+                    let inside = (0.0..display.0 as f64).contains(&x)
+                        && (0.0..display.1 as f64).contains(&y);
+                    if !inside {
+                        report(i, Problem::OutsideDisplay { x, y, display });
+                    }
+                }
+            }
+            EventType::MouseMoveRelative { .. } | EventType::Wheel { .. } => {}
+        }
+    }
+
+    let held = keys
+        .into_iter()
+        .map(|(key, i)| (i, Problem::KeyNeverReleased(key)))
+        .chain(
+            buttons
+                .into_iter()
+                .map(|(button, i)| (i, Problem::ButtonNeverReleased(button))),
+        );
+    for (i, problem) in held {
+        report(i, problem);
+    }
+    diagnostics.sort_by_key(|d| d.index);
+
+    if session.events.is_empty() {
+        diagnostics.push(Diagnostic {
+            index: None,
+            problem: Problem::Empty,
+        });
+    } else if session.total_time.is_zero() {
+        diagnostics.push(Diagnostic {
+            index: None,
+            problem: Problem::ZeroDuration,
+        });
+    }
+    diagnostics
+}
+
+/// Change made to a session by `fix`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Change {
+    /// The event at `index` was removed because of `problem`
+    Removed {
+        index: usize,
+        event: EventType,
+        problem: Problem,
+    },
+    /// The press of a stop key at `index` at the end of the session was removed
+    RemovedStopKey { index: usize, key: Key },
+    /// A release was added at the end of the session
+    Added(EventType),
+}
+
+impl Change {
+    /// Index of the event in the original session the change applies to.
+    pub fn index(&self) -> Option<usize> {
+        match *self {
+            Change::Removed { index, .. } | Change::RemovedStopKey { index, .. } => Some(index),
+            Change::Added(_) => None,
+        }
+    }
+}
+
+impl Display for Change {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match *self {
+            Change::Removed { event, problem, .. } => {
+                write!(f, "removed {}: {}", event, problem)
+            }
+            Change::RemovedStopKey { key, .. } => {
+                write!(f, "removed key press {}: stop key", key.to_str())
+            }
+            Change::Added(event) => write!(f, "added {} at the end", event),
+        }
+    }
+}
+
+/// Rewrite a session so that every press has a matching release.
+///
+/// Presses of the keys in `stop_key` at the end of the session are left over from stopping the
+/// recording and are removed first. Releases without a press and presses of something that is
+/// already held are dropped, and releases are added at the end for everything still held. The
+/// delay of a removed event is added to the next event so the timing of the session is kept.
+pub fn fix(session: &Session, stop_key: KeyState) -> (Session, Vec<Change>) {
+    let mut changes = Vec::new();
+    let mut removed = vec![false; session.events.len()];
+
+    // Mouse motion may still be recorded while the stop keys are pressed
+    for (i, event) in session.events.iter().enumerate().rev() {
+        match event.event {
+            EventType::MouseMove { .. } | EventType::MouseMoveRelative { .. } => {}
+            EventType::KeyPress(key) if stop_key.is_pressed(key) => {
+                removed[i] = true;
+                changes.push(Change::RemovedStopKey { index: i, key });
+            }
+            _ => break,
+        }
+    }
+    changes.reverse();
+
+    let mut keys = KeyState::default();
+    let mut buttons = MouseState::default();
+    for (i, event) in session.events.iter().enumerate() {
+        if removed[i] {
+            continue;
+        }
+        let problem = match event.event {
+            EventType::KeyPress(key) | EventType::KeyRelease(key) if key == Key::Unknown => None,
+            EventType::KeyPress(key) if keys.is_pressed(key) => {
+                Some(Problem::DuplicateKeyPress(key))
+            }
+            EventType::KeyRelease(key) if !keys.is_pressed(key) => {
+                Some(Problem::UnmatchedKeyRelease(key))
+            }
+            EventType::ButtonPress(button) if buttons.is_pressed(button) => {
+                Some(Problem::DuplicateButtonPress(button))
+            }
+            EventType::ButtonRelease(button) if !buttons.is_pressed(button) => {
+                Some(Problem::UnmatchedButtonRelease(button))
+            }
+            EventType::KeyPress(key) => {
+                keys.set_pressed(key);
+                None
+            }
+            EventType::KeyRelease(key) => {
+                keys.set_released(key);
+                None
+            }
+            EventType::ButtonPress(button) => {
+                buttons.set_pressed(button);
+                None
+            }
+            EventType::ButtonRelease(button) => {
+                buttons.set_released(button);
+                None
+            }
+            _ => None,
+        };
+        if let Some(problem) = problem {
+            removed[i] = true;
+            changes.push(Change::Removed {
+                index: i,
+                event: event.event,
+                problem,
+            });
+        }
+    }
+    changes.sort_by_key(|c| c.index());
+
+    let mut events = Vec::new();
+    let mut delay = Duration::ZERO;
+    for (event, removed) in session.events.iter().zip(&removed) {
+        delay += event.delay;
+        if !removed {
+            events.push(Event {
+                delay: std::mem::take(&mut delay),
+                ..event.clone()
+            });
+        }
+    }
+
+    let releases = keys
+        .iter()
+        .map(EventType::KeyRelease)
+        .chain(buttons.iter().map(EventType::ButtonRelease));
+    for event in releases {
+        changes.push(Change::Added(event));
+        events.push(Event {
+            delay: Duration::ZERO,
+            event,
+            device: None,
+        });
+    }
+
+    let mut fixed = Session::new(events, session.display);
+    fixed.devices = session.devices.clone();
+    fixed.markers = session
+        .markers
+        .iter()
+        .map(|m| Marker {
+            name: m.name.clone(),
+            index: m.index
+                - removed[..m.index.min(removed.len())]
+                    .iter()
+                    .filter(|r| **r)
+                    .count(),
+        })
+        .collect();
+    (fixed, changes)
+}
+
+/// Line numbers, starting at 1, of the events in the contents of a session file.
+pub fn event_lines(contents: &str) -> Vec<usize> {
+    contents
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+        .map(|(i, _)| i + 1)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn find_problems() {
+        let contents = "#display,100,100\n10,kp,1\n0,kp,1\n#marker,m\n0,kr,1\n0,kr,2\n0,mp,1\n\
+                        0,mm,50,120\n0,kp,85\n";
+        let session = Session::from_str(contents).unwrap();
+        let problems: Vec<_> = check(&session)
+            .into_iter()
+            .map(|d| (d.index, d.problem))
+            .collect();
+        assert_eq!(
+            problems,
+            vec![
+                (Some(1), Problem::DuplicateKeyPress(Key::A)),
+                (Some(3), Problem::UnmatchedKeyRelease(Key::B)),
+                (Some(4), Problem::ButtonNeverReleased(MouseButton::Left)),
+                (
+                    Some(5),
+                    Problem::OutsideDisplay {
+                        x: 50.0,
+                        y: 120.0,
+                        display: (100, 100)
+                    }
+                ),
+                (Some(6), Problem::UnknownKey),
+            ]
+        );
+        assert_eq!(event_lines(contents), vec![2, 3, 5, 6, 7, 8, 9]);
+
+        let empty = check(&Session::from_str("#display,100,100\n").unwrap());
+        assert_eq!(empty[0].problem, Problem::Empty);
+        assert!(check(&Session::from_str("10,kp,1\n0,kr,1").unwrap()).is_empty());
+    }
+
+    #[test]
+    fn fix_unbalanced_session() {
+        let session = Session::from_str(
+            "10,kp,1\n20,kp,1\n30,kr,1\n40,kr,2\n#marker,m\n50,kp,62\n60,mp,1\n\
+             70,kp,60\n80,mm,5,5\n90,kp,74\n",
+        )
+        .unwrap();
+        let stop_key = KeyState::with_pressed(&[Key::LCtrl, Key::Capslock]);
+        let (fixed, changes) = fix(&session, stop_key);
+
+        assert_eq!(
+            changes,
+            vec![
+                Change::Removed {
+                    index: 1,
+                    event: EventType::KeyPress(Key::A),
+                    problem: Problem::DuplicateKeyPress(Key::A)
+                },
+                Change::Removed {
+                    index: 3,
+                    event: EventType::KeyRelease(Key::B),
+                    problem: Problem::UnmatchedKeyRelease(Key::B)
+                },
+                Change::RemovedStopKey {
+                    index: 6,
+                    key: Key::LCtrl
+                },
+                Change::RemovedStopKey {
+                    index: 8,
+                    key: Key::Capslock
+                },
+                Change::Added(EventType::KeyRelease(Key::LShift)),
+                Change::Added(EventType::ButtonRelease(MouseButton::Left)),
+            ]
+        );
+        assert_eq!(
+            fixed.to_string(),
+            "10,kp,1\n50,kr,1\n#marker,m\n90,kp,62\n60,mp,1\n150,mm,5,5\n0,kr,62\n0,mr,1\n"
+        );
+        assert!(check(&fixed).is_empty());
+    }
+}
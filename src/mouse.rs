@@ -40,6 +40,17 @@ pub enum MouseButton {
     Unknown,
 }
 
+impl MouseButton {
+    pub fn to_str(self) -> &'static str {
+        match self {
+            MouseButton::Left => "left",
+            MouseButton::Right => "right",
+            MouseButton::Middle => "middle",
+            MouseButton::Unknown => "unknown",
+        }
+    }
+}
+
 impl From<u8> for MouseButton {
     fn from(value: u8) -> Self {
         match value {
@@ -111,3 +122,72 @@ impl IntoIterator for MouseState {
         MouseIterator::new(self)
     }
 }
+
+/// Connection used to query the current position of the mouse pointer.
+///
+/// rdev does not expose the position of the pointer so this talks to the platform directly. On
+/// platforms that are not supported `Pointer::open` returns `None`.
+pub struct Pointer {
+    #[cfg(target_os = "linux")]
+    display: *mut x11::xlib::Display,
+}
+
+// SAFETY: The display connection is owned by the pointer and never shared, so it is only ever used
+// by a single thread at a time.
+#[cfg(target_os = "linux")]
+unsafe impl Send for Pointer {}
+
+#[cfg(target_os = "linux")]
+impl Pointer {
+    pub fn open() -> Option<Self> {
+        // SAFETY: A null pointer opens the display named by the `DISPLAY` environment variable.
+        let display = unsafe { x11::xlib::XOpenDisplay(std::ptr::null()) };
+        (!display.is_null()).then(|| Self { display })
+    }
+
+    pub fn position(&self) -> Option<(f64, f64)> {
+        let (mut root, mut child) = (0, 0);
+        let (mut root_x, mut root_y, mut win_x, mut win_y) = (0, 0, 0, 0);
+        let mut mask = 0;
+
+        // SAFETY: The display is valid for the lifetime of `self` and all out parameters point to
+        // valid memory.
+        let found = unsafe {
+            let window = x11::xlib::XDefaultRootWindow(self.display);
+            x11::xlib::XQueryPointer(
+                self.display,
+                window,
+                &mut root,
+                &mut child,
+                &mut root_x,
+                &mut root_y,
+                &mut win_x,
+                &mut win_y,
+                &mut mask,
+            )
+        };
+
+        (found != 0).then_some((root_x as f64, root_y as f64))
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl Drop for Pointer {
+    fn drop(&mut self) {
+        // SAFETY: The display was opened in `Pointer::open` and is not used after this point.
+        unsafe {
+            x11::xlib::XCloseDisplay(self.display);
+        }
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+impl Pointer {
+    pub fn open() -> Option<Self> {
+        None
+    }
+
+    pub fn position(&self) -> Option<(f64, f64)> {
+        None
+    }
+}
@@ -0,0 +1,534 @@
+use std::{
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use crossbeam_channel::{unbounded, Receiver, Sender, TryRecvError};
+
+use crate::{
+    backend::InputSink,
+    event::EventType,
+    humanize::Humanizer,
+    report::Report,
+    schedule::Timeline,
+    session::{InputState, Session},
+};
+
+/// Determines how many times the session is played.
+#[derive(Debug, Clone, Copy)]
+pub enum Limit {
+    Iterations(u32),
+    /// Keep starting new iterations until the duration has passed. With `immediate` playback
+    /// stops in the middle of an iteration once the duration is reached.
+    Duration {
+        duration: Duration,
+        immediate: bool,
+    },
+    Forever,
+}
+
+impl Limit {
+    /// Check if another iteration should be started after `iteration` iterations have been played.
+    fn has_next(&self, iteration: u32, elapsed: Duration) -> bool {
+        match *self {
+            Limit::Iterations(n) => iteration < n,
+            Limit::Duration { duration, .. } => elapsed < duration,
+            Limit::Forever => true,
+        }
+    }
+
+    /// Time left until playback has to stop in the middle of an iteration.
+    fn remaining(&self, elapsed: Duration) -> Option<Duration> {
+        match *self {
+            Limit::Duration {
+                duration,
+                immediate: true,
+            } => Some(duration.saturating_sub(elapsed)),
+            _ => None,
+        }
+    }
+}
+
+/// Progress of the playback reported by a `PlayerHandle`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PlayerEvent {
+    /// The given number of iterations have been completed
+    Iteration(u32),
+    /// The event at `index` in the session was played at `time` on the playback timeline
+    Event {
+        index: usize,
+        time: Duration,
+        event: EventType,
+    },
+    Paused,
+    Resumed,
+    /// Playback was stopped before the limit was reached
+    Stopped,
+    /// Playback is done, no more events follow
+    Completed,
+}
+
+enum Control {
+    Pause,
+    Resume,
+    Stop,
+}
+
+/// Plays a session on an `InputSink`.
+///
+/// ```no_run
+/// use mkrp::{backend::RdevSink, player::Limit, Player, Session};
+///
+/// let session = Session::from_file("session.txt")?;
+/// let handle = Player::new(session)
+///     .limit(Limit::Iterations(3))
+///     .start(Box::new(RdevSink::new()));
+/// let report = handle.wait()?;
+/// # Ok::<(), eyre::Report>(())
+/// ```
+pub struct Player {
+    session: Session,
+    limit: Limit,
+    delay: Duration,
+    humanizer: Option<Humanizer>,
+    dry_run: bool,
+    no_delay: bool,
+}
+
+impl Player {
+    pub fn new(session: Session) -> Self {
+        Self {
+            session,
+            limit: Limit::Iterations(1),
+            delay: Duration::ZERO,
+            humanizer: None,
+            dry_run: false,
+            no_delay: false,
+        }
+    }
+
+    /// How many times the session is played.
+    pub fn limit(mut self, limit: Limit) -> Self {
+        self.limit = limit;
+        self
+    }
+
+    /// Delay between iterations.
+    pub fn delay(mut self, delay: Duration) -> Self {
+        self.delay = delay;
+        self
+    }
+
+    /// Randomly perturb every iteration with `humanizer`.
+    pub fn humanizer(mut self, humanizer: Humanizer) -> Self {
+        self.humanizer = Some(humanizer);
+        self
+    }
+
+    /// Walk through the session without simulating any input.
+    pub fn dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    /// Do not wait for the delay between events. Only has an effect on a dry run.
+    pub fn no_delay(mut self, no_delay: bool) -> Self {
+        self.no_delay = no_delay && self.dry_run;
+        self
+    }
+
+    /// Start playing the session on `sink` in the background.
+    pub fn start(self, mut sink: Box<dyn InputSink>) -> PlayerHandle {
+        let (control_tx, control_rx) = unbounded();
+        let (tx, rx) = unbounded();
+        let thread = thread::spawn(move || self.run(sink.as_mut(), control_rx, tx));
+
+        PlayerHandle {
+            control: PlayerControl(control_tx),
+            events: rx,
+            thread,
+        }
+    }
+
+    /// Play the session until the limit is reached or playback is stopped.
+    ///
+    /// Progress is reported over `tx`. Anything that is still held down at the end is released.
+    fn run(
+        mut self,
+        sink: &mut dyn InputSink,
+        control: Receiver<Control>,
+        tx: Sender<PlayerEvent>,
+    ) -> Report {
+        let mut state = InputState::default();
+        let mut timeline = Timeline::start();
+        let mut current_iteration = 0;
+        let mut report = Report::default();
+        let mut in_iteration = false;
+        let mut stopped = false;
+        // NOTE: Limits are checked against the time that has actually passed. The timeline does not
+        // advance for a session without delays, which would otherwise repeat forever. Skipped
+        // delays still count so a dry run without delays ends after the same number of iterations.
+        let progress = |timeline: &Timeline| timeline.elapsed().max(timeline.scheduled());
+        'outer: while self.limit.has_next(current_iteration, progress(&timeline)) {
+            if current_iteration > 0 {
+                // No iteration starts after the duration is up, so there is no point in waiting
+                // past it. The wait ends on the timeline, so the delay is capped against it.
+                let delay = match self.limit {
+                    Limit::Duration { duration, .. } => self
+                        .delay
+                        .min(duration.saturating_sub(timeline.scheduled())),
+                    _ => self.delay,
+                };
+                if self.no_delay {
+                    timeline.skip(delay);
+                } else {
+                    timeline.wait(delay);
+                }
+
+                // The limit might have been reached while waiting
+                if !self.limit.has_next(current_iteration, progress(&timeline)) {
+                    break;
+                }
+            }
+            in_iteration = true;
+
+            let humanized;
+            let events = match self.humanizer.as_mut() {
+                Some(h) => {
+                    humanized = h.apply(&self.session);
+                    &humanized.events
+                }
+                None => &self.session.events,
+            };
+
+            for (i, event) in events.iter().enumerate() {
+                if !handle_control(&control, &mut timeline, &tx) {
+                    stopped = true;
+                    break 'outer;
+                }
+
+                if let Some(remaining) = self.limit.remaining(progress(&timeline)) {
+                    if event.delay > remaining {
+                        timeline.wait(remaining);
+                        break 'outer;
+                    }
+                }
+
+                let error = if self.no_delay {
+                    timeline.skip(event.delay);
+                    Duration::ZERO
+                } else {
+                    timeline.wait(event.delay)
+                };
+
+                if !self.dry_run {
+                    match sink.simulate(&event.event) {
+                        Ok(()) => report.add_event(error),
+                        Err(_) => report.add_failure(),
+                    }
+                    state.apply(&event.event);
+                }
+                tx.send(PlayerEvent::Event {
+                    index: i,
+                    time: timeline.scheduled(),
+                    event: event.event,
+                })
+                .ok();
+            }
+
+            report.add_iteration(timeline.drift());
+            in_iteration = false;
+            current_iteration += 1;
+            tx.send(PlayerEvent::Iteration(current_iteration)).ok();
+        }
+
+        // Playback stopped in the middle of an iteration
+        if in_iteration {
+            report.add_iteration(timeline.drift());
+        }
+
+        // Checking the state of keys and mouse and unset anything that is recorded as pressed
+        let releases = state
+            .keys
+            .iter()
+            .map(EventType::KeyRelease)
+            .chain(state.buttons.iter().map(EventType::ButtonRelease));
+        for event in releases {
+            if sink.simulate(&event).is_err() {
+                report.add_failure();
+            }
+        }
+
+        if stopped {
+            tx.send(PlayerEvent::Stopped).ok();
+        }
+        tx.send(PlayerEvent::Completed).ok();
+
+        report
+    }
+}
+
+/// Process pending control messages, blocking while playback is paused.
+///
+/// Time spent paused is left out of the timeline. Returns `false` if playback should stop.
+fn handle_control(
+    control: &Receiver<Control>,
+    timeline: &mut Timeline,
+    tx: &Sender<PlayerEvent>,
+) -> bool {
+    loop {
+        match control.try_recv() {
+            Ok(Control::Stop) => return false,
+            Ok(Control::Pause) => {
+                let paused = Instant::now();
+                tx.send(PlayerEvent::Paused).ok();
+                loop {
+                    match control.recv() {
+                        Ok(Control::Resume) => break,
+                        Ok(Control::Pause) => {}
+                        Ok(Control::Stop) | Err(_) => return false,
+                    }
+                }
+                timeline.shift(paused.elapsed());
+                tx.send(PlayerEvent::Resumed).ok();
+            }
+            Ok(Control::Resume) => {}
+            Err(TryRecvError::Empty | TryRecvError::Disconnected) => return true,
+        }
+    }
+}
+
+/// Controls a running playback. Can be cloned and sent to other threads.
+#[derive(Debug, Clone)]
+pub struct PlayerControl(Sender<Control>);
+
+impl PlayerControl {
+    /// Pause playback before the next event.
+    pub fn pause(&self) {
+        self.0.send(Control::Pause).ok();
+    }
+
+    pub fn resume(&self) {
+        self.0.send(Control::Resume).ok();
+    }
+
+    /// Stop playback before the next event and release everything that is held down.
+    pub fn stop(&self) {
+        self.0.send(Control::Stop).ok();
+    }
+}
+
+/// Handle to a playback running in the background.
+pub struct PlayerHandle {
+    control: PlayerControl,
+    events: Receiver<PlayerEvent>,
+    thread: JoinHandle<Report>,
+}
+
+impl PlayerHandle {
+    pub fn control(&self) -> PlayerControl {
+        self.control.clone()
+    }
+
+    pub fn pause(&self) {
+        self.control.pause();
+    }
+
+    pub fn resume(&self) {
+        self.control.resume();
+    }
+
+    pub fn stop(&self) {
+        self.control.stop();
+    }
+
+    /// Progress of the playback.
+    pub fn events(&self) -> &Receiver<PlayerEvent> {
+        &self.events
+    }
+
+    /// Wait until playback is done and return the timing report.
+    pub fn wait(self) -> eyre::Result<Report> {
+        self.thread
+            .join()
+            .map_err(|_| eyre::eyre!("Player thread panicked"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{backend::MockSink, keys::Key};
+    use std::str::FromStr;
+
+    #[test]
+    fn play_iterations_and_release_held_keys() {
+        let sink = MockSink::default();
+        let session = Session::from_str("0,mm,10,20\n0,kp,1\n0,md,5,-5\n0,kp,2\n0,kr,2").unwrap();
+        let handle = Player::new(session)
+            .limit(Limit::Iterations(2))
+            .start(Box::new(sink.clone()));
+
+        let report = handle.wait().unwrap().summary();
+        assert_eq!(report.events, 10);
+        assert_eq!(report.failures, 0);
+        let events = sink.events();
+        assert_eq!(events.len(), 11);
+        assert_eq!(
+            events[2],
+            EventType::MouseMoveRelative { dx: 5.0, dy: -5.0 }
+        );
+        assert_eq!(events[10], EventType::KeyRelease(Key::A));
+    }
+
+    #[test]
+    fn replay_relative_motion() {
+        let sink = MockSink::default();
+        let session = Session::from_str("0,md,5,-5\n10,md,-15,0\n0,mm,10,20\n10,md,1,1").unwrap();
+        let report = Player::new(session)
+            .start(Box::new(sink.clone()))
+            .wait()
+            .unwrap()
+            .summary();
+
+        assert_eq!(report.failures, 0);
+        assert_eq!(
+            sink.events(),
+            vec![
+                EventType::MouseMoveRelative { dx: 5.0, dy: -5.0 },
+                EventType::MouseMoveRelative { dx: -15.0, dy: 0.0 },
+                EventType::MouseMove { x: 10.0, y: 20.0 },
+                EventType::MouseMoveRelative { dx: 1.0, dy: 1.0 },
+            ]
+        );
+    }
+
+    #[test]
+    fn duration_limit_with_zero_length_session() {
+        let sink = MockSink::default();
+        let session = Session::from_str("0,kp,1\n0,kr,1").unwrap();
+        let handle = Player::new(session)
+            .limit(Limit::Duration {
+                duration: Duration::from_millis(20),
+                immediate: false,
+            })
+            .start(Box::new(sink.clone()));
+
+        let (tx, rx) = unbounded();
+        thread::spawn(move || tx.send(handle.wait().unwrap()).ok());
+        let report = rx
+            .recv_timeout(Duration::from_secs(5))
+            .expect("playback did not stop after the duration");
+        assert!(report.summary().events > 0);
+    }
+
+    #[test]
+    fn duration_limit_during_delays() {
+        let sink = MockSink::default();
+        let session = Session::from_str("0,kp,1\n10,kr,1").unwrap();
+        let started = Instant::now();
+        let report = Player::new(session)
+            .limit(Limit::Duration {
+                duration: Duration::from_millis(30),
+                immediate: false,
+            })
+            .delay(Duration::from_millis(100))
+            .start(Box::new(sink.clone()))
+            .wait()
+            .unwrap();
+        assert_eq!(report.summary().iteration_drift_ms.len(), 1);
+        assert_eq!(sink.events().len(), 2);
+        assert!(started.elapsed() < Duration::from_millis(100));
+
+        let sink = MockSink::default();
+        let session = Session::from_str("0,kp,1\n500,kr,1").unwrap();
+        let started = Instant::now();
+        Player::new(session)
+            .limit(Limit::Duration {
+                duration: Duration::from_millis(30),
+                immediate: true,
+            })
+            .start(Box::new(sink.clone()))
+            .wait()
+            .unwrap();
+        assert!(started.elapsed() < Duration::from_millis(500));
+        assert_eq!(
+            sink.events(),
+            vec![EventType::KeyPress(Key::A), EventType::KeyRelease(Key::A)]
+        );
+    }
+
+    #[test]
+    fn stop_forever_through_control() {
+        let sink = MockSink::default();
+        let session = Session::from_str("0,kp,1\n10,kr,1").unwrap();
+        let handle = Player::new(session)
+            .limit(Limit::Forever)
+            .start(Box::new(sink.clone()));
+        thread::sleep(Duration::from_millis(50));
+        handle.control().stop();
+
+        let events = handle.events().clone();
+        handle.wait().unwrap();
+        let events: Vec<_> = events.try_iter().collect();
+        assert!(events.contains(&PlayerEvent::Stopped));
+        assert_eq!(events.last(), Some(&PlayerEvent::Completed));
+        assert!(sink.events().len() >= 2);
+    }
+
+    #[test]
+    fn dry_run_without_delays() {
+        let contents = "0,kp,1\n200,mm,10,10\n200,kr,1";
+        let sink = MockSink::default();
+        let started = Instant::now();
+        let handle = Player::new(Session::from_str(contents).unwrap())
+            .dry_run(true)
+            .no_delay(true)
+            .start(Box::new(sink.clone()));
+        let events = handle.events().clone();
+        let report = handle.wait().unwrap();
+
+        assert!(started.elapsed() < Duration::from_millis(400));
+        assert!(sink.events().is_empty());
+        assert_eq!(report.summary().events, 0);
+        let played = events
+            .try_iter()
+            .filter(|e| matches!(e, PlayerEvent::Event { .. }))
+            .count();
+        assert_eq!(played, 3);
+
+        // Delays are only skipped on a dry run
+        let started = Instant::now();
+        Player::new(Session::from_str(contents).unwrap())
+            .no_delay(true)
+            .start(Box::new(sink.clone()))
+            .wait()
+            .unwrap();
+        assert!(started.elapsed() >= Duration::from_millis(400));
+        assert_eq!(sink.events().len(), 3);
+    }
+
+    #[test]
+    fn stop_while_paused() {
+        let sink = MockSink::default();
+        let session = Session::from_str("0,kp,1\n0,kr,1").unwrap();
+        let (control_tx, control_rx) = unbounded();
+        let (tx, rx) = unbounded();
+        control_tx.send(Control::Pause).unwrap();
+        control_tx.send(Control::Stop).unwrap();
+
+        let report = Player::new(session).run(&mut sink.clone(), control_rx, tx);
+        assert_eq!(report.summary().events, 0);
+        assert!(sink.events().is_empty());
+
+        let events: Vec<_> = rx.try_iter().collect();
+        assert_eq!(
+            events,
+            vec![
+                PlayerEvent::Paused,
+                PlayerEvent::Stopped,
+                PlayerEvent::Completed
+            ]
+        );
+    }
+}
@@ -0,0 +1,546 @@
+use std::{
+    sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender},
+    thread::{self, JoinHandle},
+    time::{Duration, Instant, SystemTime},
+};
+
+use crate::{
+    backend::{InputEvent, InputSource},
+    event::{Event, EventType},
+    keys::{Key, KeyState},
+    session::{Marker, Session},
+};
+
+/// Reason a recording stopped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopReason {
+    /// The stop key combination was pressed
+    StopKey,
+    /// The maximum duration was reached
+    Duration,
+    /// No input was received for the idle timeout
+    Idle,
+    /// The maximum number of events was recorded
+    EventLimit,
+    /// The recording was stopped through its handle
+    Stopped,
+    /// The input source stopped sending events
+    Disconnected,
+}
+
+/// Progress of a recording passed to the callback of a `Recorder`.
+#[derive(Debug, Clone)]
+pub enum RecorderEvent {
+    /// An event was added to the session
+    Recorded(Event),
+    /// A marker was placed
+    Marker(Marker),
+    /// Recording stopped, no more events follow
+    Stopped(StopReason),
+}
+
+/// Callback invoked for every `RecorderEvent`.
+pub type RecorderCallback = Box<dyn FnMut(&RecorderEvent) + Send>;
+
+enum Message {
+    Input(InputEvent),
+    Failed(eyre::Report),
+    Stop,
+}
+
+/// Records events of an `InputSource` into a session.
+///
+/// ```no_run
+/// use mkrp::{backend::RdevSource, Recorder};
+///
+/// let handle = Recorder::default().start(Box::new(RdevSource));
+/// // Recording stops when escape is pressed
+/// let session = handle.wait()?;
+/// # Ok::<(), eyre::Report>(())
+/// ```
+pub struct Recorder {
+    stop_key: KeyState,
+    max_duration: Option<Duration>,
+    max_events: Option<usize>,
+    idle_timeout: Option<Duration>,
+    marker_key: Option<Key>,
+    relative_motion: bool,
+    display: Option<(u64, u64)>,
+    position: Option<(f64, f64)>,
+    callback: Option<RecorderCallback>,
+}
+
+impl Default for Recorder {
+    fn default() -> Self {
+        Self {
+            stop_key: KeyState::with_pressed(&[Key::Escape]),
+            max_duration: None,
+            max_events: None,
+            idle_timeout: None,
+            marker_key: None,
+            relative_motion: false,
+            display: None,
+            position: None,
+            callback: None,
+        }
+    }
+}
+
+impl Recorder {
+    /// Keys that stop the recording when they are held down at the same time.
+    pub fn stop_key(mut self, keys: KeyState) -> Self {
+        self.stop_key = keys;
+        self
+    }
+
+    /// Stop recording after the given amount of time.
+    pub fn max_duration(mut self, duration: Duration) -> Self {
+        self.max_duration = Some(duration);
+        self
+    }
+
+    /// Stop recording after the given number of events.
+    pub fn max_events(mut self, count: usize) -> Self {
+        self.max_events = Some(count);
+        self
+    }
+
+    /// Stop recording when no event has been recorded for the given duration.
+    pub fn idle_timeout(mut self, timeout: Duration) -> Self {
+        self.idle_timeout = Some(timeout);
+        self
+    }
+
+    /// Key that places a marker instead of being recorded.
+    pub fn marker_key(mut self, key: Key) -> Self {
+        self.marker_key = Some(key);
+        self
+    }
+
+    /// Record mouse motion as the distance moved instead of absolute positions.
+    pub fn relative_motion(mut self, relative: bool) -> Self {
+        self.relative_motion = relative;
+        self
+    }
+
+    /// Resolution of the display stored in the session.
+    pub fn display(mut self, display: Option<(u64, u64)>) -> Self {
+        self.display = display;
+        self
+    }
+
+    /// Position of the pointer when recording starts, used as the starting point of relative
+    /// motion. Without it the first mouse move is used instead.
+    pub fn position(mut self, position: Option<(f64, f64)>) -> Self {
+        self.position = position;
+        self
+    }
+
+    /// Call `callback` for every recorded event, placed marker and when recording stops.
+    pub fn on_event(mut self, callback: impl FnMut(&RecorderEvent) + Send + 'static) -> Self {
+        self.callback = Some(Box::new(callback));
+        self
+    }
+
+    /// Start recording from `source` in the background.
+    ///
+    /// Sources that never stop on their own, like the ones listening to real devices, keep their
+    /// listener thread running after the recording stopped.
+    pub fn start(self, source: Box<dyn InputSource>) -> RecorderHandle {
+        let (tx, rx) = channel();
+        let start = SystemTime::now();
+        let devices = source.devices();
+
+        let listener_tx = tx.clone();
+        thread::spawn(move || {
+            let event_tx = listener_tx.clone();
+            let result = source.listen(Box::new(move |event| {
+                event_tx.send(Message::Input(event)).ok();
+            }));
+            if let Err(e) = result {
+                listener_tx.send(Message::Failed(e)).ok();
+            }
+        });
+
+        let thread = thread::spawn(move || {
+            let mut session = self.record(rx, start)?;
+            session.devices = devices;
+            Ok(session)
+        });
+
+        RecorderHandle { tx, thread }
+    }
+
+    /// Record events received from `rx` until one of the stop conditions is met.
+    fn record(mut self, rx: Receiver<Message>, start: SystemTime) -> eyre::Result<Session> {
+        let mut prev_system_time = start;
+        let started = Instant::now();
+        let mut last_input: Option<Instant> = None;
+
+        let mut last_position = self.position;
+
+        let mut keystate = KeyState::default();
+        let mut events = Vec::new();
+        let mut markers = Vec::new();
+        let mut marker_held = false;
+        let reason = loop {
+            // The next point in time where recording should stop on its own if no other event has
+            // been received until then.
+            let deadline = [
+                self.max_duration.map(|d| started + d),
+                self.idle_timeout.zip(last_input).map(|(d, t)| t + d),
+            ]
+            .into_iter()
+            .flatten()
+            .min();
+
+            let message = match deadline {
+                Some(deadline) => {
+                    match rx.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+                        Ok(message) => message,
+                        Err(RecvTimeoutError::Timeout) => {
+                            if self.max_duration.is_some_and(|d| started.elapsed() >= d) {
+                                break StopReason::Duration;
+                            } else {
+                                break StopReason::Idle;
+                            }
+                        }
+                        Err(RecvTimeoutError::Disconnected) => break StopReason::Disconnected,
+                    }
+                }
+                None => match rx.recv() {
+                    Ok(message) => message,
+                    Err(_) => break StopReason::Disconnected,
+                },
+            };
+            let event = match message {
+                Message::Input(event) => event,
+                Message::Failed(e) => return Err(e),
+                Message::Stop => break StopReason::Stopped,
+            };
+
+            // NOTE: Sources that read several devices on their own threads can deliver events
+            // slightly out of order. Those are recorded without delay instead of failing.
+            let duration = || {
+                event
+                    .time
+                    .duration_since(prev_system_time)
+                    .unwrap_or(Duration::ZERO)
+            };
+            let recorded = match event.event {
+                EventType::KeyPress(key) if self.marker_key == Some(key) => {
+                    if !marker_held {
+                        marker_held = true;
+                        let marker = Marker {
+                            name: format!("m{}", markers.len() + 1),
+                            index: events.len(),
+                        };
+                        self.notify(&RecorderEvent::Marker(marker.clone()));
+                        markers.push(marker);
+                    }
+                    None
+                }
+                EventType::KeyRelease(key) if self.marker_key == Some(key) => {
+                    marker_held = false;
+                    None
+                }
+                EventType::KeyPress(key) => {
+                    if keystate.is_pressed(key) {
+                        None
+                    } else {
+                        // NOTE: Check if pressing this key satifies the stop state. This has to be
+                        // checked first because we dont want to record the event as iterations
+                        // would stop after the first iteration.
+                        keystate.set_pressed(key);
+                        if keystate.is_state_held(self.stop_key) {
+                            break StopReason::StopKey;
+                        }
+                        Some((duration(), event.event))
+                    }
+                }
+                EventType::KeyRelease(key) => {
+                    if keystate.is_pressed(key) {
+                        keystate.set_released(key);
+                        Some((duration(), event.event))
+                    } else {
+                        None
+                    }
+                }
+                EventType::MouseMove { x, y } => {
+                    let duration = duration();
+                    if duration.as_millis() >= 1 {
+                        // NOTE: Relative motion is computed against the last recorded position so
+                        // that the motion of skipped events is carried over into the next one.
+                        let event_type = match (self.relative_motion, last_position) {
+                            (false, _) => Some(EventType::MouseMove { x, y }),
+                            (true, Some((px, py))) => Some(EventType::MouseMoveRelative {
+                                dx: x - px,
+                                dy: y - py,
+                            }),
+                            (true, None) => None,
+                        };
+                        last_position = Some((x, y));
+                        event_type.map(|e| (duration, e))
+                    } else {
+                        None
+                    }
+                }
+                e => Some((duration(), e)),
+            };
+
+            if let Some((delay, event_type)) = recorded {
+                let recorded = Event {
+                    delay,
+                    event: event_type,
+                    device: event.device,
+                };
+                self.notify(&RecorderEvent::Recorded(recorded.clone()));
+                events.push(recorded);
+                prev_system_time = prev_system_time.max(event.time);
+
+                // Only events that made it into the recording count as input. This way the idle
+                // tail is never stored as the idle timer is not reset by ignored events.
+                last_input = Some(Instant::now());
+            }
+
+            if self.max_events.is_some_and(|max| events.len() >= max) {
+                break StopReason::EventLimit;
+            }
+        };
+        self.notify(&RecorderEvent::Stopped(reason));
+
+        let mut session = Session::new(events, self.display);
+        session.markers = markers;
+        Ok(session)
+    }
+
+    fn notify(&mut self, event: &RecorderEvent) {
+        if let Some(callback) = self.callback.as_mut() {
+            callback(event);
+        }
+    }
+}
+
+/// Handle to a recording running in the background.
+pub struct RecorderHandle {
+    tx: Sender<Message>,
+    thread: JoinHandle<eyre::Result<Session>>,
+}
+
+impl RecorderHandle {
+    /// Stop recording and return the recorded session.
+    pub fn stop(self) -> eyre::Result<Session> {
+        self.tx.send(Message::Stop).ok();
+        self.wait()
+    }
+
+    /// Wait until the recording stops on its own and return the recorded session.
+    pub fn wait(self) -> eyre::Result<Session> {
+        self.thread
+            .join()
+            .map_err(|_| eyre::eyre!("Recorder thread panicked"))?
+    }
+
+    /// Check if the recording has stopped.
+    pub fn is_finished(&self) -> bool {
+        self.thread.is_finished()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+    use crate::{backend::MockSource, mouse::MouseButton};
+
+    fn record(recorder: Recorder, events: &[(u64, Option<u16>, EventType)]) -> Session {
+        let start = SystemTime::UNIX_EPOCH;
+        let (tx, rx) = channel();
+        for &(ms, device, event) in events {
+            tx.send(Message::Input(InputEvent {
+                time: start + Duration::from_millis(ms),
+                event,
+                device,
+            }))
+            .unwrap();
+        }
+        drop(tx);
+        recorder.record(rx, start).unwrap()
+    }
+
+    #[test]
+    fn record_until_stop_key() {
+        let session = record(
+            Recorder::default()
+                .marker_key(Key::F8)
+                .display(Some((1920, 1080))),
+            &[
+                (100, None, EventType::KeyPress(Key::A)),
+                (150, None, EventType::KeyPress(Key::A)),
+                (200, None, EventType::KeyPress(Key::F8)),
+                (250, None, EventType::KeyRelease(Key::F8)),
+                (300, None, EventType::KeyRelease(Key::A)),
+                (400, None, EventType::KeyPress(Key::Escape)),
+                (500, None, EventType::KeyPress(Key::B)),
+            ],
+        );
+
+        let events: Vec<_> = session.events.iter().map(|e| (e.delay, e.event)).collect();
+        assert_eq!(
+            events,
+            vec![
+                (Duration::from_millis(100), EventType::KeyPress(Key::A)),
+                (Duration::from_millis(200), EventType::KeyRelease(Key::A)),
+            ]
+        );
+        assert_eq!(
+            session.markers,
+            vec![Marker {
+                name: "m1".to_string(),
+                index: 1
+            }]
+        );
+        assert_eq!(session.display, Some((1920, 1080)));
+    }
+
+    #[test]
+    fn record_relative_motion_and_event_limit() {
+        let session = record(
+            Recorder::default().relative_motion(true).max_events(2),
+            &[
+                (10, None, EventType::MouseMove { x: 10.0, y: 10.0 }),
+                (20, None, EventType::MouseMove { x: 15.0, y: 8.0 }),
+                (30, None, EventType::MouseMove { x: 20.0, y: 20.0 }),
+                (40, None, EventType::MouseMove { x: 0.0, y: 0.0 }),
+            ],
+        );
+
+        let events: Vec<_> = session.events.iter().map(|e| e.event).collect();
+        assert_eq!(
+            events,
+            vec![
+                EventType::MouseMoveRelative { dx: 5.0, dy: -2.0 },
+                EventType::MouseMoveRelative { dx: 5.0, dy: 12.0 },
+            ]
+        );
+    }
+
+    #[test]
+    fn record_device_of_events() {
+        let session = record(
+            Recorder::default(),
+            &[
+                (10, Some(1), EventType::KeyPress(Key::A)),
+                (20, Some(0), EventType::ButtonPress(MouseButton::Left)),
+                (30, Some(1), EventType::KeyRelease(Key::A)),
+            ],
+        );
+
+        let devices: Vec<_> = session.events.iter().map(|e| e.device).collect();
+        assert_eq!(devices, vec![Some(1), Some(0), Some(1)]);
+        assert_eq!(session.events[0].to_string(), "10,kp,1,1");
+    }
+
+    #[test]
+    fn record_events_out_of_order() {
+        let session = record(
+            Recorder::default(),
+            &[
+                (100, Some(0), EventType::KeyPress(Key::A)),
+                (90, Some(1), EventType::ButtonPress(MouseButton::Left)),
+                (120, Some(0), EventType::KeyRelease(Key::A)),
+            ],
+        );
+
+        let delays: Vec<_> = session.events.iter().map(|e| e.delay.as_millis()).collect();
+        assert_eq!(delays, vec![100, 0, 20]);
+    }
+
+    /// Recorder that collects the reasons it stopped for.
+    fn with_reasons(recorder: Recorder) -> (Recorder, Arc<Mutex<Vec<StopReason>>>) {
+        let reasons = Arc::new(Mutex::new(Vec::new()));
+        let stopped = reasons.clone();
+        let recorder = recorder.on_event(move |event| {
+            if let RecorderEvent::Stopped(reason) = event {
+                stopped.lock().unwrap().push(*reason);
+            }
+        });
+        (recorder, reasons)
+    }
+
+    #[test]
+    fn record_event_limit_from_source() {
+        let start = SystemTime::now();
+        let events = (1..=4)
+            .map(|i| InputEvent {
+                time: start + Duration::from_millis(i * 10),
+                event: EventType::ButtonPress(MouseButton::Left),
+                device: None,
+            })
+            .collect();
+        let (recorder, reasons) = with_reasons(Recorder::default().max_events(3));
+        let session = recorder
+            .start(Box::new(MockSource { events }))
+            .wait()
+            .unwrap();
+
+        assert_eq!(session.events.len(), 3);
+        assert_eq!(*reasons.lock().unwrap(), vec![StopReason::EventLimit]);
+    }
+
+    #[test]
+    fn record_until_max_duration() {
+        let start = SystemTime::now();
+        let (tx, rx) = channel();
+        tx.send(Message::Input(InputEvent {
+            time: start + Duration::from_millis(10),
+            event: EventType::KeyPress(Key::A),
+            device: None,
+        }))
+        .unwrap();
+
+        // The sender is kept alive so only the duration limit can stop the recording
+        let (recorder, reasons) =
+            with_reasons(Recorder::default().max_duration(Duration::from_millis(50)));
+        let started = Instant::now();
+        let session = recorder.record(rx, start).unwrap();
+        drop(tx);
+
+        assert!(started.elapsed() >= Duration::from_millis(50));
+        assert_eq!(session.events.len(), 1);
+        assert_eq!(*reasons.lock().unwrap(), vec![StopReason::Duration]);
+    }
+
+    #[test]
+    fn record_until_idle() {
+        let start = SystemTime::now();
+        let (tx, rx) = channel();
+        let events = [
+            (10, EventType::KeyPress(Key::A)),
+            (20, EventType::KeyRelease(Key::A)),
+            // Ignored as B is not pressed, so it does not count as input
+            (30, EventType::KeyRelease(Key::B)),
+        ];
+        for (ms, event) in events {
+            tx.send(Message::Input(InputEvent {
+                time: start + Duration::from_millis(ms),
+                event,
+                device: None,
+            }))
+            .unwrap();
+        }
+
+        // The sender is kept alive so only the idle timeout can stop the recording
+        let (recorder, reasons) =
+            with_reasons(Recorder::default().idle_timeout(Duration::from_millis(50)));
+        let started = Instant::now();
+        let session = recorder.record(rx, start).unwrap();
+        drop(tx);
+
+        assert!(started.elapsed() >= Duration::from_millis(50));
+        assert_eq!(session.events.len(), 2);
+        assert_eq!(session.total_time, Duration::from_millis(20));
+        assert_eq!(*reasons.lock().unwrap(), vec![StopReason::Idle]);
+    }
+}
@@ -0,0 +1,119 @@
+use std::{fmt::Display, time::Duration};
+
+use serde::Serialize;
+
+/// Timing measurements collected while playing a session.
+#[derive(Debug, Default)]
+pub struct Report {
+    errors: Vec<Duration>,
+    failures: usize,
+    iteration_drift: Vec<Duration>,
+}
+
+impl Report {
+    /// Record an event that was simulated `error` later than its scheduled time.
+    pub fn add_event(&mut self, error: Duration) {
+        self.errors.push(error);
+    }
+
+    /// Record an event that could not be simulated.
+    pub fn add_failure(&mut self) {
+        self.failures += 1;
+    }
+
+    /// Record the drift of the timeline at the end of an iteration.
+    pub fn add_iteration(&mut self, drift: Duration) {
+        self.iteration_drift.push(drift);
+    }
+
+    pub fn summary(&self) -> Summary {
+        let mut errors = self.errors.clone();
+        errors.sort();
+
+        let mean = if errors.is_empty() {
+            Duration::ZERO
+        } else {
+            errors.iter().sum::<Duration>() / errors.len() as u32
+        };
+        let p99 = match errors.len() {
+            0 => Duration::ZERO,
+            n => errors[(n * 99).div_ceil(100) - 1],
+        };
+        let max = errors.last().copied().unwrap_or_default();
+
+        Summary {
+            events: errors.len(),
+            failures: self.failures,
+            mean_error_ms: as_millis(mean),
+            p99_error_ms: as_millis(p99),
+            max_error_ms: as_millis(max),
+            iteration_drift_ms: self
+                .iteration_drift
+                .iter()
+                .copied()
+                .map(as_millis)
+                .collect(),
+        }
+    }
+}
+
+fn as_millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+/// Statistics computed from a `Report`.
+#[derive(Debug, Serialize)]
+pub struct Summary {
+    pub events: usize,
+    pub failures: usize,
+    pub mean_error_ms: f64,
+    pub p99_error_ms: f64,
+    pub max_error_ms: f64,
+    pub iteration_drift_ms: Vec<f64>,
+}
+
+impl Display for Summary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Events simulated: {}", self.events)?;
+        writeln!(f, "Failed events:    {}", self.failures)?;
+        writeln!(
+            f,
+            "Timing error:     mean {:.3}ms, p99 {:.3}ms, max {:.3}ms",
+            self.mean_error_ms, self.p99_error_ms, self.max_error_ms
+        )?;
+        for (i, drift) in self.iteration_drift_ms.iter().enumerate() {
+            writeln!(f, "Iteration {:>5}:  drift {:.3}ms", i + 1, drift)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn summary_statistics() {
+        let mut report = Report::default();
+        for ms in 1..=100 {
+            report.add_event(Duration::from_millis(ms));
+        }
+        report.add_failure();
+        report.add_iteration(Duration::from_millis(3));
+
+        let summary = report.summary();
+        assert_eq!(summary.events, 100);
+        assert_eq!(summary.failures, 1);
+        assert_eq!(summary.mean_error_ms, 50.5);
+        assert_eq!(summary.p99_error_ms, 99.0);
+        assert_eq!(summary.max_error_ms, 100.0);
+        assert_eq!(summary.iteration_drift_ms, vec![3.0]);
+    }
+
+    #[test]
+    fn empty_summary() {
+        let summary = Report::default().summary();
+        assert_eq!(summary.events, 0);
+        assert_eq!(summary.max_error_ms, 0.0);
+    }
+}
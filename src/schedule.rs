@@ -0,0 +1,140 @@
+use std::time::{Duration, Instant};
+
+/// Schedules events against an absolute timeline.
+///
+/// Every delay is added to the point in time the previous event was scheduled at instead of the
+/// time it was actually executed. Time spent simulating events therefore does not accumulate and
+/// the timeline catches up whenever it falls behind.
+#[derive(Debug, Clone, Copy)]
+pub struct Timeline {
+    start: Instant,
+    offset: Duration,
+}
+
+impl Timeline {
+    pub fn start() -> Self {
+        Self {
+            start: Instant::now(),
+            offset: Duration::ZERO,
+        }
+    }
+
+    /// Advance the timeline by `delay` and wait until that point in time has been reached.
+    ///
+    /// If the timeline is already behind, this returns immediately. The returned value is how late
+    /// the call returned compared to the scheduled time.
+    pub fn wait(&mut self, delay: Duration) -> Duration {
+        self.offset += delay;
+        let deadline = self.start + self.offset;
+        let now = Instant::now();
+        if deadline > now {
+            spin_sleep::sleep(deadline - now);
+        }
+        Instant::now().saturating_duration_since(deadline)
+    }
+
+    /// Advance the timeline by `delay` without waiting.
+    pub fn skip(&mut self, delay: Duration) {
+        self.offset += delay;
+    }
+
+    /// Move the timeline forward in time so that `duration` is left out, for example while
+    /// playback is paused.
+    pub fn shift(&mut self, duration: Duration) {
+        self.start += duration;
+    }
+
+    /// Time that has actually passed since the start, leaving out shifted time.
+    pub fn elapsed(&self) -> Duration {
+        self.start.elapsed()
+    }
+
+    /// Position on the timeline of the last scheduled event.
+    pub fn scheduled(&self) -> Duration {
+        self.offset
+    }
+
+    /// How far the current time is behind the last scheduled event.
+    pub fn drift(&self) -> Duration {
+        Instant::now().saturating_duration_since(self.start + self.offset)
+    }
+}
+
+/// Format a point on a timeline as `mm:ss.mmm`, adding hours when needed.
+pub fn format_timestamp(time: Duration) -> String {
+    let millis = time.subsec_millis();
+    let secs = time.as_secs();
+    match secs / 3600 {
+        0 => format!("{:02}:{:02}.{:03}", secs / 60, secs % 60, millis),
+        hours => format!(
+            "{}:{:02}:{:02}.{:03}",
+            hours,
+            secs / 60 % 60,
+            secs % 60,
+            millis
+        ),
+    }
+}
+
+/// Parse a human readable duration such as `1h`, `1m30s` or `250ms`.
+///
+/// A plain number without any unit is interpreted as seconds.
+pub fn parse_duration(s: &str) -> Result<Duration, String> {
+    let s = s.trim();
+    if s.is_empty() {
+        return Err("empty duration".to_string());
+    }
+
+    if let Ok(secs) = s.parse::<u64>() {
+        return Ok(Duration::from_secs(secs));
+    }
+
+    let mut total = Duration::ZERO;
+    let mut rest = s;
+    while !rest.is_empty() {
+        let digits = rest
+            .find(|c: char| !c.is_ascii_digit())
+            .ok_or_else(|| format!("missing unit in duration '{}'", s))?;
+        if digits == 0 {
+            return Err(format!("invalid duration '{}'", s));
+        }
+        let value: u64 = rest[..digits]
+            .parse()
+            .map_err(|_| format!("invalid number in duration '{}'", s))?;
+        rest = &rest[digits..];
+
+        let unit_len = rest
+            .find(|c: char| c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        let unit = match &rest[..unit_len] {
+            "ms" => Duration::from_millis(value),
+            "s" => Duration::from_secs(value),
+            "m" => Duration::from_secs(value * 60),
+            "h" => Duration::from_secs(value * 60 * 60),
+            u => return Err(format!("unknown unit '{}' in duration '{}'", u, s)),
+        };
+        total += unit;
+        rest = &rest[unit_len..];
+    }
+
+    Ok(total)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_durations() {
+        assert_eq!(parse_duration("10"), Ok(Duration::from_secs(10)));
+        assert_eq!(parse_duration("250ms"), Ok(Duration::from_millis(250)));
+        assert_eq!(parse_duration("1m30s"), Ok(Duration::from_secs(90)));
+        assert_eq!(parse_duration("2h"), Ok(Duration::from_secs(7200)));
+        assert_eq!(parse_duration("1s500ms"), Ok(Duration::from_millis(1500)));
+
+        assert!(parse_duration("").is_err());
+        assert!(parse_duration("ms").is_err());
+        assert!(parse_duration("5x").is_err());
+        assert!(parse_duration("1m30").is_err());
+    }
+}
@@ -1,81 +1,816 @@
-use std::{fs::read_to_string, path::Path, time::Duration};
+use std::{collections::HashMap, fmt::Display, path::Path, str::FromStr, time::Duration};
 
-use rdev::EventType;
-
-use crate::{event::Event, keys::Key, mouse::MouseButton};
+use crate::{
+    event::{Event, EventType},
+    keys::{Key, KeyState},
+    mouse::{MouseButton, MouseState},
+    schedule::parse_duration,
+};
 
 pub struct Session {
     pub events: Vec<Event>,
     pub total_time: Duration,
+    /// Resolution of the display the session was recorded on
+    pub display: Option<(u64, u64)>,
+    /// Named positions in the session, ordered by index
+    pub markers: Vec<Marker>,
+    /// Input devices the events were recorded from
+    pub devices: Vec<Device>,
+}
+
+/// Input device referenced by the events of a session.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Device {
+    pub id: u16,
+    pub name: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Marker {
+    pub name: String,
+    /// Index of the event the marker is placed in front of
+    pub index: usize,
+}
+
+/// Position in a session given as a point in time, an event index or the name of a marker.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Position {
+    Time(Duration),
+    Index(usize),
+    Marker(String),
+}
+
+impl FromStr for Position {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.is_empty() {
+            return Err("empty position".to_string());
+        }
+
+        if let Ok(index) = s.parse::<usize>() {
+            return Ok(Position::Index(index));
+        }
+
+        Ok(match parse_duration(s) {
+            Ok(time) => Position::Time(time),
+            Err(_) => Position::Marker(s.to_string()),
+        })
+    }
+}
+
+impl Display for Position {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Position::Time(time) => write!(f, "{:?}", time),
+            Position::Index(index) => write!(f, "{}", index),
+            Position::Marker(name) => write!(f, "{}", name),
+        }
+    }
+}
+
+/// State of the keyboard and mouse at a given point in a session.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct InputState {
+    pub keys: KeyState,
+    pub buttons: MouseState,
+    /// Last absolute position of the mouse
+    pub position: Option<(f64, f64)>,
+}
+
+impl InputState {
+    pub fn apply(&mut self, event: &EventType) {
+        match *event {
+            EventType::KeyPress(key) => self.keys.set_pressed(key),
+            EventType::KeyRelease(key) => self.keys.set_released(key),
+            EventType::ButtonPress(button) => self.buttons.set_pressed(button),
+            EventType::ButtonRelease(button) => self.buttons.set_released(button),
+            EventType::MouseMove { x, y } => self.position = Some((x, y)),
+            EventType::MouseMoveRelative { dx, dy } => {
+                self.position = self.position.map(|(x, y)| (x + dx, y + dy))
+            }
+            EventType::Wheel { .. } => {}
+        }
+    }
 }
 
 impl Session {
-    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, std::io::Error> {
-        let contents = std::fs::read_to_string(path.as_ref())?;
-        Ok(Self::from_str(&contents))
+    pub fn new(events: Vec<Event>, display: Option<(u64, u64)>) -> Self {
+        let total_time = events.iter().map(|e| e.delay).sum();
+        Self {
+            events,
+            total_time,
+            display,
+            markers: Vec::new(),
+            devices: Vec::new(),
+        }
     }
 
-    pub fn from_str(contents: &str) -> Self {
-        let mut total_time = Duration::ZERO;
-        let mut events = Vec::new();
-        for line in contents.lines() {
-            let mut values = line.split(',');
-            let delay_value = values.next().map(|s| s.parse().unwrap()).unwrap();
-
-            let delay = Duration::from_millis(delay_value);
-            let event = match values.next().unwrap() {
-                "kp" => {
-                    let key = Key::from(values.next().map(|s| s.parse::<u8>().unwrap()).unwrap());
-                    Event {
-                        delay,
-                        event: EventType::KeyPress(key.into()),
+    pub fn from_file<P: AsRef<Path>>(path: P) -> eyre::Result<Self> {
+        let path = path.as_ref();
+        let contents = std::fs::read_to_string(path)?;
+        contents
+            .parse()
+            .map_err(|e| eyre::eyre!("{}: {}", path.display(), e))
+    }
+
+    pub fn to_file<P: AsRef<Path>>(&self, path: P) -> Result<(), std::io::Error> {
+        std::fs::write(path, self.to_string())
+    }
+
+    /// Time from the start of the session until the event at `index` is played.
+    pub fn time_at(&self, index: usize) -> Duration {
+        self.events[..index.min(self.events.len())]
+            .iter()
+            .map(|e| e.delay)
+            .sum()
+    }
+
+    /// Resolve a position into the index of the event at that position.
+    ///
+    /// A point in time resolves to the first event played at or after that time.
+    pub fn index_of(&self, position: &Position) -> Option<usize> {
+        match position {
+            Position::Index(index) => (*index <= self.events.len()).then_some(*index),
+            Position::Time(time) => {
+                let mut elapsed = Duration::ZERO;
+                for (i, event) in self.events.iter().enumerate() {
+                    elapsed += event.delay;
+                    if elapsed >= *time {
+                        return Some(i);
                     }
                 }
-                "kr" => {
-                    let key = Key::from(values.next().map(|s| s.parse::<u8>().unwrap()).unwrap());
-                    Event {
-                        delay,
-                        event: EventType::KeyRelease(key.into()),
-                    }
+                Some(self.events.len())
+            }
+            Position::Marker(name) => self
+                .markers
+                .iter()
+                .find(|m| &m.name == name)
+                .map(|m| m.index),
+        }
+    }
+
+    /// State of the keyboard and mouse right before the event at `index` is played.
+    pub fn state_at(&self, index: usize) -> InputState {
+        let mut state = InputState::default();
+        for event in &self.events[..index.min(self.events.len())] {
+            state.apply(&event.event);
+        }
+        state
+    }
+
+    /// Create a new session from the events in `start..end`.
+    ///
+    /// The new session starts by restoring the state of the keyboard and mouse at `start`, pressing
+    /// any key or button that is held down at that point. Everything still held down at the end is
+    /// released so that the resulting session is balanced.
+    pub fn range(&self, start: usize, end: usize) -> Session {
+        let end = end.min(self.events.len());
+        let start = start.min(end);
+        let initial = self.state_at(start);
+
+        let mut events = Vec::new();
+        if let Some((x, y)) = initial.position {
+            events.push(Event {
+                delay: Duration::ZERO,
+                event: EventType::MouseMove { x, y },
+                device: None,
+            });
+        }
+        for key in initial.keys {
+            events.push(Event {
+                delay: Duration::ZERO,
+                event: EventType::KeyPress(key),
+                device: None,
+            });
+        }
+        for button in initial.buttons {
+            events.push(Event {
+                delay: Duration::ZERO,
+                event: EventType::ButtonPress(button),
+                device: None,
+            });
+        }
+
+        let offset = events.len();
+        events.extend_from_slice(&self.events[start..end]);
+
+        let mut state = InputState::default();
+        for event in &events {
+            state.apply(&event.event);
+        }
+        for key in state.keys {
+            events.push(Event {
+                delay: Duration::ZERO,
+                event: EventType::KeyRelease(key),
+                device: None,
+            });
+        }
+        for button in state.buttons {
+            events.push(Event {
+                delay: Duration::ZERO,
+                event: EventType::ButtonRelease(button),
+                device: None,
+            });
+        }
+
+        let mut session = Session::new(events, self.display);
+        session.devices = self.devices.clone();
+        session.markers = self
+            .markers
+            .iter()
+            // Markers after the last event belong to the range if it reaches the end
+            .filter(|m| {
+                (start..end).contains(&m.index) || (m.index == end && end == self.events.len())
+            })
+            .map(|m| Marker {
+                name: m.name.clone(),
+                index: m.index - start + offset,
+            })
+            .collect();
+        session
+    }
+
+    /// Create a new session without the events in `start..end`.
+    ///
+    /// Everything held down at `start` is released before the gap and pressed again after it, so
+    /// the resulting session stays balanced.
+    pub fn cut(&self, start: usize, end: usize) -> Session {
+        let mut session = self.range(0, start);
+        session.append(self.range(end, self.events.len()), Duration::ZERO);
+        session
+    }
+
+    /// Append the events of `other`, played `gap` after the last event of this session.
+    ///
+    /// Everything still held down at the end of either session is released. Mouse coordinates of
+    /// `other` are scaled to the display of this session and devices with the same name are
+    /// merged.
+    pub fn append(&mut self, mut other: Session, gap: Duration) {
+        self.release_held();
+        other.release_held();
+
+        match (other.display, self.display) {
+            (Some(from), Some(to)) if from != to => other.scale(from, to),
+            (display, None) => self.display = display,
+            _ => {}
+        }
+
+        let mut ids = HashMap::new();
+        for device in other.devices {
+            let id = match self.devices.iter().find(|d| d.name == device.name) {
+                Some(existing) => existing.id,
+                None => {
+                    let id = self.devices.iter().map(|d| d.id + 1).max().unwrap_or(0);
+                    self.devices.push(Device {
+                        id,
+                        name: device.name,
+                    });
+                    id
                 }
-                "mp" => {
-                    let button =
-                        MouseButton::from(values.next().map(|s| s.parse::<u8>().unwrap()).unwrap());
-                    Event {
-                        delay,
-                        event: EventType::ButtonPress(button.into()),
-                    }
+            };
+            ids.insert(device.id, id);
+        }
+
+        let offset = self.events.len();
+        self.markers
+            .extend(other.markers.into_iter().map(|m| Marker {
+                name: m.name,
+                index: m.index + offset,
+            }));
+        for (i, mut event) in other.events.into_iter().enumerate() {
+            if i == 0 {
+                event.delay += gap;
+            }
+            event.device = event.device.map(|id| ids.get(&id).copied().unwrap_or(id));
+            self.events.push(event);
+        }
+        self.update_total_time();
+    }
+
+    /// Release every key and button that is still held down at the end of the session.
+    fn release_held(&mut self) {
+        let state = self.state_at(self.events.len());
+        let releases = state
+            .keys
+            .iter()
+            .map(EventType::KeyRelease)
+            .chain(state.buttons.iter().map(EventType::ButtonRelease));
+        for event in releases {
+            self.events.push(Event {
+                delay: Duration::ZERO,
+                event,
+                device: None,
+            });
+        }
+    }
+
+    /// Scale the delay of every event so the session plays at the given speed multiplier.
+    pub fn set_speed(&mut self, speed: f64) {
+        for event in &mut self.events {
+            event.delay = event.delay.div_f64(speed);
+        }
+        self.update_total_time();
+    }
+
+    /// Shorten every delay longer than `max` to `max`.
+    pub fn cap_delay(&mut self, max: Duration) {
+        for event in &mut self.events {
+            event.delay = event.delay.min(max);
+        }
+        self.update_total_time();
+    }
+
+    /// Remove the idle time before the first key press, click or scroll and every event after
+    /// the last key or button event.
+    ///
+    /// Mouse moves before the first key press, click or scroll are kept so the pointer still
+    /// ends up at the right position, but they are played without delay.
+    pub fn trim(&mut self) {
+        let is_input = |e: &Event| {
+            matches!(
+                e.event,
+                EventType::KeyPress(_)
+                    | EventType::KeyRelease(_)
+                    | EventType::ButtonPress(_)
+                    | EventType::ButtonRelease(_)
+            )
+        };
+        let end = self.events.iter().rposition(is_input).map_or(0, |i| i + 1);
+        self.events.truncate(end);
+        self.markers.retain(|m| m.index <= end);
+
+        let first = self
+            .events
+            .iter()
+            .position(|e| is_input(e) || matches!(e.event, EventType::Wheel { .. }))
+            .unwrap_or(0);
+        for event in self.events.iter_mut().take(first + 1) {
+            event.delay = Duration::ZERO;
+        }
+        self.update_total_time();
+    }
+
+    /// Scale all mouse coordinates from the resolution `from` to the resolution `to`.
+    pub fn scale(&mut self, from: (u64, u64), to: (u64, u64)) {
+        let sx = to.0 as f64 / from.0 as f64;
+        let sy = to.1 as f64 / from.1 as f64;
+        for event in &mut self.events {
+            match &mut event.event {
+                EventType::MouseMove { x, y } => {
+                    *x = (*x * sx).round();
+                    *y = (*y * sy).round();
                 }
-                "mr" => {
-                    let button =
-                        MouseButton::from(values.next().map(|s| s.parse::<u8>().unwrap()).unwrap());
-                    Event {
-                        delay,
-                        event: EventType::ButtonRelease(button.into()),
-                    }
+                EventType::MouseMoveRelative { dx, dy } => {
+                    *dx = (*dx * sx).round();
+                    *dy = (*dy * sy).round();
                 }
-                "mm" => {
-                    let x = values.next().map(|s| s.parse::<f64>().unwrap()).unwrap();
-                    let y = values.next().map(|s| s.parse::<f64>().unwrap()).unwrap();
-                    Event {
-                        delay,
-                        event: EventType::MouseMove { x, y },
+                _ => {}
+            }
+        }
+    }
+
+    /// First absolute mouse position in the session.
+    pub fn first_position(&self) -> Option<(f64, f64)> {
+        self.events.iter().find_map(|e| match e.event {
+            EventType::MouseMove { x, y } => Some((x, y)),
+            _ => None,
+        })
+    }
+
+    /// Move all absolute mouse coordinates by the given offset.
+    pub fn translate(&mut self, offset_x: f64, offset_y: f64) {
+        for event in &mut self.events {
+            if let EventType::MouseMove { x, y } = &mut event.event {
+                *x += offset_x;
+                *y += offset_y;
+            }
+        }
+    }
+
+    /// Move all absolute mouse coordinates so that the first one lands on `position`.
+    ///
+    /// Playing the session then starts from `position` and everything else happens relative to
+    /// it. Sessions without absolute mouse coordinates are left as is.
+    pub fn translate_to(&mut self, (x, y): (f64, f64)) {
+        if let Some((first_x, first_y)) = self.first_position() {
+            self.translate(x - first_x, y - first_y);
+        }
+    }
+
+    /// Limit all absolute mouse coordinates to a display of the given resolution.
+    pub fn clamp(&mut self, display: (u64, u64)) {
+        let max_x = display.0.saturating_sub(1) as f64;
+        let max_y = display.1.saturating_sub(1) as f64;
+        for event in &mut self.events {
+            if let EventType::MouseMove { x, y } = &mut event.event {
+                *x = x.clamp(0.0, max_x);
+                *y = y.clamp(0.0, max_y);
+            }
+        }
+    }
+
+    fn update_total_time(&mut self) {
+        self.total_time = self.events.iter().map(|e| e.delay).sum();
+    }
+}
+
+/// Error for a line of a session file that cannot be parsed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    /// Line number, starting at 1
+    pub line: usize,
+    pub message: String,
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// Parse the next comma separated value of a line.
+fn next_value<'a, T: FromStr>(
+    values: &mut impl Iterator<Item = &'a str>,
+    name: &str,
+) -> Result<T, String> {
+    let value = values.next().ok_or_else(|| format!("missing {}", name))?;
+    value
+        .parse()
+        .map_err(|_| format!("invalid {} '{}'", name, value))
+}
+
+/// Parse an event line of a session file.
+fn parse_event(line: &str) -> Result<Event, String> {
+    let mut values = line.split(',');
+    let delay = Duration::from_millis(next_value(&mut values, "delay")?);
+    let event = match values.next().ok_or("missing event type")? {
+        "kp" => EventType::KeyPress(Key::from(next_value::<u8>(&mut values, "key")?)),
+        "kr" => EventType::KeyRelease(Key::from(next_value::<u8>(&mut values, "key")?)),
+        "mp" => {
+            let button = next_value::<u8>(&mut values, "button")?;
+            EventType::ButtonPress(MouseButton::from(button))
+        }
+        "mr" => {
+            let button = next_value::<u8>(&mut values, "button")?;
+            EventType::ButtonRelease(MouseButton::from(button))
+        }
+        "mm" => EventType::MouseMove {
+            x: next_value(&mut values, "x coordinate")?,
+            y: next_value(&mut values, "y coordinate")?,
+        },
+        "md" => EventType::MouseMoveRelative {
+            dx: next_value(&mut values, "x offset")?,
+            dy: next_value(&mut values, "y offset")?,
+        },
+        "mw" => EventType::Wheel {
+            delta_x: next_value(&mut values, "horizontal scroll")?,
+            delta_y: next_value(&mut values, "vertical scroll")?,
+        },
+        tag => return Err(format!("unknown event type '{}'", tag)),
+    };
+
+    // Events recorded with a known device carry its id as an extra last column
+    let device = match values.next() {
+        Some(id) => Some(id.parse().map_err(|_| format!("invalid device '{}'", id))?),
+        None => None,
+    };
+    Ok(Event {
+        delay,
+        event,
+        device,
+    })
+}
+
+impl FromStr for Session {
+    type Err = ParseError;
+
+    fn from_str(contents: &str) -> Result<Self, Self::Err> {
+        let mut events = Vec::new();
+        let mut display = None;
+        let mut markers = Vec::new();
+        let mut devices = Vec::new();
+        for (i, line) in contents.lines().enumerate() {
+            let error = |message| ParseError {
+                line: i + 1,
+                message,
+            };
+            if line.is_empty() {
+                continue;
+            }
+
+            // Lines starting with `#` contain metadata about the session. Unknown entries are
+            // ignored.
+            if let Some(meta) = line.strip_prefix('#') {
+                let (key, value) = meta.split_once(',').unwrap_or((meta, ""));
+                match key {
+                    "display" => {
+                        let mut values = value.split(',');
+                        let width = next_value(&mut values, "display width").map_err(error)?;
+                        let height = next_value(&mut values, "display height").map_err(error)?;
+                        if width == 0 || height == 0 {
+                            return Err(error(format!(
+                                "display {}x{} must not be empty",
+                                width, height
+                            )));
+                        }
+                        display = Some((width, height));
                     }
-                }
-                "mw" => {
-                    let delta_x = values.next().map(|s| s.parse::<i64>().unwrap()).unwrap();
-                    let delta_y = values.next().map(|s| s.parse::<i64>().unwrap()).unwrap();
-                    Event {
-                        delay,
-                        event: EventType::Wheel { delta_x, delta_y },
+                    "marker" => markers.push(Marker {
+                        name: value.to_string(),
+                        index: events.len(),
+                    }),
+                    "device" => {
+                        let mut values = value.splitn(2, ',');
+                        let id = next_value(&mut values, "device id").map_err(error)?;
+                        devices.push(Device {
+                            id,
+                            name: values.next().unwrap_or_default().to_string(),
+                        });
                     }
+                    _ => {}
                 }
-                _ => todo!(),
-            };
-            events.push(event);
-            total_time += delay;
+                continue;
+            }
+
+            events.push(parse_event(line).map_err(error)?);
+        }
+
+        let mut session = Session::new(events, display);
+        session.markers = markers;
+        session.devices = devices;
+        Ok(session)
+    }
+}
+
+impl Display for Session {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some((width, height)) = self.display {
+            writeln!(f, "#display,{},{}", width, height)?;
+        }
+        for device in &self.devices {
+            writeln!(f, "#device,{},{}", device.id, device.name)?;
+        }
+        let mut markers = self.markers.iter().peekable();
+        for (i, event) in self.events.iter().enumerate() {
+            while let Some(marker) = markers.next_if(|m| m.index <= i) {
+                writeln!(f, "#marker,{}", marker.name)?;
+            }
+            writeln!(f, "{}", event)?;
+        }
+        for marker in markers {
+            writeln!(f, "#marker,{}", marker.name)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::{InputSink, MockSink};
+
+    #[test]
+    fn scale_and_cap_delays() {
+        let mut session = Session::from_str("100,kp,1\n3000,kr,1\n500,mm,10,20").unwrap();
+        assert_eq!(session.total_time, Duration::from_millis(3600));
+
+        session.set_speed(2.0);
+        assert_eq!(session.events[1].delay, Duration::from_millis(1500));
+        assert_eq!(session.total_time, Duration::from_millis(1800));
+
+        session.cap_delay(Duration::from_millis(200));
+        assert_eq!(session.events[0].delay, Duration::from_millis(50));
+        assert_eq!(session.events[1].delay, Duration::from_millis(200));
+        assert_eq!(session.total_time, Duration::from_millis(450));
+    }
+
+    #[test]
+    fn trim_head_and_tail() {
+        let mut session = Session::from_str(
+            "2000,mm,1,1\n500,mm,5,5\n300,mp,1\n100,mr,1\n#marker,end\n800,mm,9,9",
+        )
+        .unwrap();
+        session.trim();
+        assert_eq!(
+            session.to_string(),
+            "0,mm,1,1\n0,mm,5,5\n0,mp,1\n100,mr,1\n#marker,end\n"
+        );
+        assert_eq!(session.total_time, Duration::from_millis(100));
+
+        let mut session = Session::from_str("100,mm,1,1\n").unwrap();
+        session.trim();
+        assert!(session.events.is_empty());
+    }
+
+    #[test]
+    fn report_parse_errors() {
+        let error = |contents: &str| contents.parse::<Session>().err().map(|e| e.to_string());
+        assert_eq!(
+            error("0,kp,1\n\n0,xx,1"),
+            Some("line 3: unknown event type 'xx'".to_string())
+        );
+        assert_eq!(
+            error("0,mm,10"),
+            Some("line 1: missing y coordinate".to_string())
+        );
+        assert_eq!(
+            error("#display,1920,abc"),
+            Some("line 1: invalid display height 'abc'".to_string())
+        );
+        assert_eq!(
+            error("#display,0,0\n0,mm,1,1"),
+            Some("line 1: display 0x0 must not be empty".to_string())
+        );
+        assert_eq!(
+            error("0,kp,1\n1.5,kr,1"),
+            Some("line 2: invalid delay '1.5'".to_string())
+        );
+        assert_eq!(
+            error("0,kp,1,x"),
+            Some("line 1: invalid device 'x'".to_string())
+        );
+    }
+
+    #[test]
+    fn read_and_write_display() {
+        let contents = "#display,2560,1440\n10,mm,1280,720\n5,kp,3\n";
+        let session = Session::from_str(contents).unwrap();
+        assert_eq!(session.display, Some((2560, 1440)));
+        assert_eq!(session.events.len(), 2);
+        assert_eq!(session.to_string(), contents);
+
+        let session = Session::from_str("10,mm,1280,720").unwrap();
+        assert_eq!(session.display, None);
+    }
+
+    #[test]
+    fn read_and_write_devices() {
+        let contents =
+            "#device,0,Macro Pad, Rev 2\n#device,1,Keyboard\n5,kp,3,1\n5,kr,3,0\n5,mw,0,-1\n";
+        let session = Session::from_str(contents).unwrap();
+        assert_eq!(
+            session.devices[0],
+            Device {
+                id: 0,
+                name: "Macro Pad, Rev 2".to_string()
+            }
+        );
+        assert_eq!(session.events[0].device, Some(1));
+        assert_eq!(session.events[2].device, None);
+        assert_eq!(session.to_string(), contents);
+    }
+
+    #[test]
+    fn read_and_write_relative_motion() {
+        let contents = "0,mm,10,10\n16,md,-5,3\n8,md,0,-12,1\n";
+        let session = Session::from_str(contents).unwrap();
+        let events: Vec<_> = session.events.iter().map(|e| e.event).collect();
+        assert_eq!(
+            events,
+            vec![
+                EventType::MouseMove { x: 10.0, y: 10.0 },
+                EventType::MouseMoveRelative { dx: -5.0, dy: 3.0 },
+                EventType::MouseMoveRelative { dx: 0.0, dy: -12.0 },
+            ]
+        );
+        assert_eq!(session.events[2].device, Some(1));
+        assert_eq!(session.to_string(), contents);
+        assert_eq!(session.first_position(), Some((10.0, 10.0)));
+    }
+
+    #[test]
+    fn remap_coordinates() {
+        let mut session = Session::from_str("0,mm,2559,0\n0,mm,1280,720\n0,md,20,-10").unwrap();
+        session.scale((2560, 1440), (1920, 1080));
+        session.translate(10.0, -10.0);
+        session.clamp((1920, 1080));
+
+        let events: Vec<_> = session.events.iter().map(|e| e.event).collect();
+        assert_eq!(
+            events,
+            vec![
+                EventType::MouseMove { x: 1919.0, y: 0.0 },
+                EventType::MouseMove { x: 970.0, y: 530.0 },
+                EventType::MouseMoveRelative { dx: 15.0, dy: -8.0 },
+            ]
+        );
+    }
+
+    #[test]
+    fn translate_to_pointer() {
+        let mut sink = MockSink {
+            position: Some((500.0, 300.0)),
+            ..Default::default()
+        };
+        let mut session = Session::from_str("0,mm,100,100\n0,md,5,5\n0,mm,110,90").unwrap();
+        session.translate_to(sink.position().unwrap());
+        for event in &session.events {
+            sink.simulate(&event.event).unwrap();
         }
 
-        Self { events, total_time }
+        assert_eq!(
+            sink.events(),
+            vec![
+                EventType::MouseMove { x: 500.0, y: 300.0 },
+                EventType::MouseMoveRelative { dx: 5.0, dy: 5.0 },
+                EventType::MouseMove { x: 510.0, y: 290.0 },
+            ]
+        );
+
+        let mut keys = Session::from_str("0,kp,1\n0,kr,1").unwrap();
+        keys.translate_to((500.0, 300.0));
+        assert_eq!(keys.to_string(), "0,kp,1\n0,kr,1\n");
+    }
+
+    #[test]
+    fn parse_positions() {
+        assert_eq!("12".parse(), Ok(Position::Index(12)));
+        assert_eq!("1m30s".parse(), Ok(Position::Time(Duration::from_secs(90))));
+        assert_eq!("m2".parse(), Ok(Position::Marker("m2".to_string())));
+    }
+
+    #[test]
+    fn range_restores_state() {
+        let contents = "0,mm,5,5\n100,kp,60\n#marker,m1\n100,kp,1\n100,kr,1\n100,kr,60\n";
+        let session = Session::from_str(contents).unwrap();
+        assert_eq!(session.to_string(), contents);
+
+        let start = session.index_of(&Position::Marker("m1".to_string()));
+        assert_eq!(start, Some(2));
+        assert_eq!(
+            session.index_of(&Position::Time(Duration::from_millis(150))),
+            Some(2)
+        );
+        assert_eq!(session.index_of(&Position::Index(9)), None);
+
+        let range = session.range(2, 4);
+        let events: Vec<_> = range.events.iter().map(|e| e.event).collect();
+        assert_eq!(
+            events,
+            vec![
+                EventType::MouseMove { x: 5.0, y: 5.0 },
+                EventType::KeyPress(Key::LCtrl),
+                EventType::KeyPress(Key::A),
+                EventType::KeyRelease(Key::A),
+                EventType::KeyRelease(Key::LCtrl),
+            ]
+        );
+        assert_eq!(
+            range.markers,
+            vec![Marker {
+                name: "m1".to_string(),
+                index: 2
+            }]
+        );
+        assert_eq!(range.total_time, Duration::from_millis(200));
+    }
+
+    #[test]
+    fn range_keeps_trailing_marker() {
+        let session =
+            Session::from_str("0,kp,1\n100,kr,1\n0,kp,2\n50,kr,2\n#marker,end\n").unwrap();
+        let range = session.range(2, 4);
+        assert_eq!(
+            range.markers,
+            vec![Marker {
+                name: "end".to_string(),
+                index: 2
+            }]
+        );
+        assert_eq!(range.to_string(), "0,kp,2\n50,kr,2\n#marker,end\n");
+        assert!(session.range(0, 3).markers.is_empty());
+    }
+
+    #[test]
+    fn cut_and_append() {
+        let session = Session::from_str(
+            "#display,100,100\n#device,0,kbd\n0,mm,5,5\n100,kp,60,0\n100,kp,1,0\n100,kr,1,0\n\
+             #marker,m\n100,kp,2,0\n100,kr,2,0\n100,kr,60,0\n",
+        )
+        .unwrap();
+        let cut = session.cut(2, 4);
+        assert_eq!(
+            cut.to_string(),
+            "#display,100,100\n#device,0,kbd\n0,mm,5,5\n100,kp,60,0\n0,kr,60\n0,mm,5,5\n\
+             0,kp,60\n#marker,m\n100,kp,2,0\n100,kr,2,0\n100,kr,60,0\n"
+        );
+
+        let mut first =
+            Session::from_str("#display,100,100\n#device,0,mouse\n100,mp,1,0\n").unwrap();
+        let second = Session::from_str(
+            "#display,200,200\n#device,0,kbd\n#device,1,mouse\n#marker,s\n50,mm,50,50,1\n\
+             10,kp,1,0\n",
+        )
+        .unwrap();
+        first.append(second, Duration::from_millis(1000));
+        assert_eq!(
+            first.to_string(),
+            "#display,100,100\n#device,0,mouse\n#device,1,kbd\n100,mp,1,0\n0,mr,1\n#marker,s\n\
+             1050,mm,25,25,0\n10,kp,1,1\n0,kr,1\n"
+        );
+        assert_eq!(first.total_time, Duration::from_millis(1160));
     }
 }
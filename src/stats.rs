@@ -0,0 +1,200 @@
+use std::{collections::BTreeMap, fmt::Display, time::Duration};
+
+use crate::{
+    event::EventType,
+    keys::Key,
+    mouse::MouseButton,
+    schedule::format_timestamp,
+    session::{InputState, Session},
+};
+
+/// Area covered by the mouse pointer.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Bounds {
+    pub min_x: f64,
+    pub min_y: f64,
+    pub max_x: f64,
+    pub max_y: f64,
+}
+
+impl Bounds {
+    fn extend(bounds: Option<Bounds>, (x, y): (f64, f64)) -> Bounds {
+        match bounds {
+            Some(b) => Bounds {
+                min_x: b.min_x.min(x),
+                min_y: b.min_y.min(y),
+                max_x: b.max_x.max(x),
+                max_y: b.max_y.max(y),
+            },
+            None => Bounds {
+                min_x: x,
+                min_y: y,
+                max_x: x,
+                max_y: y,
+            },
+        }
+    }
+}
+
+/// Overview of a session computed without playing it.
+#[derive(Debug, Default)]
+pub struct Stats {
+    pub duration: Duration,
+    pub events: usize,
+    pub key_presses: usize,
+    pub key_releases: usize,
+    pub button_presses: usize,
+    pub button_releases: usize,
+    pub mouse_moves: usize,
+    pub relative_moves: usize,
+    pub wheel_events: usize,
+    /// Number of presses of every key used in the session
+    pub key_histogram: BTreeMap<Key, usize>,
+    /// Longest delay before an event and the index of that event
+    pub longest_idle: Option<(usize, Duration)>,
+    /// Area covered by the pointer. Relative motion is only counted once an absolute position is
+    /// known.
+    pub bounds: Option<Bounds>,
+    /// Keys still pressed at the end of the session
+    pub held_keys: Vec<Key>,
+    /// Buttons still pressed at the end of the session
+    pub held_buttons: Vec<MouseButton>,
+}
+
+impl Stats {
+    pub fn new(session: &Session) -> Self {
+        let mut stats = Stats {
+            duration: session.total_time,
+            events: session.events.len(),
+            ..Default::default()
+        };
+
+        let mut state = InputState::default();
+        for (i, event) in session.events.iter().enumerate() {
+            if stats.longest_idle.is_none_or(|(_, d)| event.delay > d) {
+                stats.longest_idle = Some((i, event.delay));
+            }
+
+            match event.event {
+                EventType::KeyPress(key) => {
+                    stats.key_presses += 1;
+                    *stats.key_histogram.entry(key).or_default() += 1;
+                }
+                EventType::KeyRelease(_) => stats.key_releases += 1,
+                EventType::ButtonPress(_) => stats.button_presses += 1,
+                EventType::ButtonRelease(_) => stats.button_releases += 1,
+                EventType::MouseMove { .. } => stats.mouse_moves += 1,
+                EventType::MouseMoveRelative { .. } => stats.relative_moves += 1,
+                EventType::Wheel { .. } => stats.wheel_events += 1,
+            }
+
+            state.apply(&event.event);
+            if let (EventType::MouseMove { .. } | EventType::MouseMoveRelative { .. }, Some(p)) =
+                (event.event, state.position)
+            {
+                stats.bounds = Some(Bounds::extend(stats.bounds, p));
+            }
+        }
+
+        stats.held_keys = state.keys.iter().collect();
+        stats.held_buttons = state.buttons.iter().collect();
+        stats
+    }
+}
+
+impl Display for Stats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Duration:         {}", format_timestamp(self.duration))?;
+        writeln!(f, "Events:           {}", self.events)?;
+        let counts = [
+            ("key press", self.key_presses),
+            ("key release", self.key_releases),
+            ("button press", self.button_presses),
+            ("button release", self.button_releases),
+            ("mouse move", self.mouse_moves),
+            ("relative move", self.relative_moves),
+            ("wheel", self.wheel_events),
+        ];
+        for (name, count) in counts.iter().filter(|(_, count)| *count > 0) {
+            writeln!(f, "  {:<16}{}", name, count)?;
+        }
+
+        writeln!(f, "Distinct keys:    {}", self.key_histogram.len())?;
+        let mut histogram: Vec<_> = self.key_histogram.iter().collect();
+        histogram.sort_by(|a, b| b.1.cmp(a.1));
+        for (key, count) in histogram {
+            writeln!(f, "  {:<16}{}", key.to_str(), count)?;
+        }
+
+        writeln!(f, "Clicks:           {}", self.button_presses)?;
+        if let Some((index, delay)) = self.longest_idle {
+            writeln!(
+                f,
+                "Longest idle:     {} before event {}",
+                format_timestamp(delay),
+                index
+            )?;
+        }
+        if let Some(b) = self.bounds {
+            writeln!(
+                f,
+                "Mouse area:       {},{} to {},{} ({}x{})",
+                b.min_x,
+                b.min_y,
+                b.max_x,
+                b.max_y,
+                b.max_x - b.min_x,
+                b.max_y - b.min_y
+            )?;
+        }
+
+        let held: Vec<_> = self
+            .held_keys
+            .iter()
+            .map(|k| k.to_str())
+            .chain(self.held_buttons.iter().map(|b| b.to_str()))
+            .collect();
+        if !held.is_empty() {
+            writeln!(f, "Held at end:      {}", held.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn session_stats() {
+        let session = Session::from_str(
+            "10,mm,100,200\n0,md,-50,10\n500,kp,1\n20,kr,1\n0,kp,1\n0,kp,62\n\
+             100,mp,1\n0,mr,1\n0,mw,0,-1\n0,mm,300,50\n",
+        )
+        .unwrap();
+        let stats = Stats::new(&session);
+
+        assert_eq!(stats.duration, Duration::from_millis(630));
+        assert_eq!(stats.events, 10);
+        assert_eq!(stats.key_presses, 3);
+        assert_eq!(stats.key_releases, 1);
+        assert_eq!(stats.button_presses, 1);
+        assert_eq!(stats.relative_moves, 1);
+        assert_eq!(stats.wheel_events, 1);
+        assert_eq!(stats.key_histogram.get(&Key::A), Some(&2));
+        assert_eq!(stats.key_histogram.len(), 2);
+        assert_eq!(stats.longest_idle, Some((2, Duration::from_millis(500))));
+        assert_eq!(
+            stats.bounds,
+            Some(Bounds {
+                min_x: 50.0,
+                min_y: 50.0,
+                max_x: 300.0,
+                max_y: 210.0
+            })
+        );
+        assert_eq!(stats.held_keys, vec![Key::A, Key::LShift]);
+        assert!(stats.held_buttons.is_empty());
+    }
+}
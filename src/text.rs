@@ -0,0 +1,197 @@
+use crate::{
+    event::EventType,
+    keys::{Key, KeyState},
+    layout::{DeadKey, Layout, Stroke},
+    session::Session,
+};
+
+/// Reconstructs the text typed by a sequence of key events.
+///
+/// The decoder keeps track of held modifiers, caps lock and dead keys and edits the text like a
+/// simple text field would: backspace and delete remove characters and the arrow, home and end
+/// keys move the cursor. Key presses while control, alt or super are held are taken to be
+/// shortcuts and do not type anything. Mouse clicks that move the cursor are not taken into
+/// account, so the result is an approximation.
+#[derive(Debug)]
+pub struct TextDecoder {
+    layout: Layout,
+    keys: KeyState,
+    caps_lock: bool,
+    dead_key: DeadKey,
+    text: Vec<char>,
+    /// Index in `text` the next character is inserted at
+    cursor: usize,
+}
+
+impl TextDecoder {
+    pub fn new(layout: Layout) -> Self {
+        Self {
+            layout,
+            keys: KeyState::default(),
+            caps_lock: false,
+            dead_key: DeadKey::default(),
+            text: Vec::new(),
+            cursor: 0,
+        }
+    }
+
+    pub fn apply(&mut self, event: &EventType) {
+        match *event {
+            EventType::KeyPress(key) => {
+                self.keys.set_pressed(key);
+                self.press(key);
+            }
+            EventType::KeyRelease(key) => self.keys.set_released(key),
+            _ => {}
+        }
+    }
+
+    pub fn text(&self) -> String {
+        self.text.iter().collect()
+    }
+
+    fn is_held(&self, keys: &[Key]) -> bool {
+        keys.iter().any(|key| self.keys.is_pressed(*key))
+    }
+
+    fn press(&mut self, key: Key) {
+        match key {
+            Key::LShift | Key::RShift | Key::LCtrl | Key::RCtrl => {}
+            Key::LAlt | Key::RAlt | Key::LSuper | Key::RSuper => {}
+            Key::Capslock => self.caps_lock = !self.caps_lock,
+            _ if self.is_held(&[Key::LCtrl, Key::RCtrl, Key::LAlt, Key::LSuper, Key::RSuper]) => {}
+            Key::Backspace => {
+                if self.dead_key.take().is_none() && self.cursor > 0 {
+                    self.cursor -= 1;
+                    self.text.remove(self.cursor);
+                }
+            }
+            Key::Delete => {
+                if self.cursor < self.text.len() {
+                    self.text.remove(self.cursor);
+                }
+            }
+            Key::Left => self.cursor = self.cursor.saturating_sub(1),
+            Key::Right => self.cursor = (self.cursor + 1).min(self.text.len()),
+            Key::Home => self.cursor = self.line_start(self.cursor),
+            Key::End => self.cursor = self.line_end(self.cursor),
+            Key::Up => {
+                let start = self.line_start(self.cursor);
+                if start > 0 {
+                    let column = self.cursor - start;
+                    self.cursor = (self.line_start(start - 1) + column).min(start - 1);
+                }
+            }
+            Key::Down => {
+                let end = self.line_end(self.cursor);
+                if end < self.text.len() {
+                    let column = self.cursor - self.line_start(self.cursor);
+                    self.cursor = (end + 1 + column).min(self.line_end(end + 1));
+                }
+            }
+            _ => self.stroke(key),
+        }
+    }
+
+    fn stroke(&mut self, key: Key) {
+        let stroke = Stroke {
+            key,
+            shift: self.is_held(&[Key::LShift, Key::RShift]),
+            altgr: self.is_held(&[Key::RAlt]),
+        };
+
+        if let Some(accent) = self.layout.accent_of(stroke) {
+            if let Some(pending) = self.dead_key.press(accent) {
+                self.insert(pending);
+            }
+            return;
+        }
+
+        let Some(mut c) = self.layout.char_of(stroke) else {
+            return;
+        };
+        if self.caps_lock && c.is_alphabetic() {
+            let shift = !stroke.shift;
+            c = match self.layout.char_of(Stroke { shift, ..stroke }) {
+                Some(other) if other.is_alphabetic() => other,
+                _ => c,
+            };
+        }
+
+        for c in self.dead_key.type_char(c) {
+            self.insert(c);
+        }
+    }
+
+    fn insert(&mut self, c: char) {
+        self.text.insert(self.cursor, c);
+        self.cursor += 1;
+    }
+
+    /// Index of the first character of the line containing `index`.
+    fn line_start(&self, index: usize) -> usize {
+        self.text[..index]
+            .iter()
+            .rposition(|c| *c == '\n')
+            .map_or(0, |i| i + 1)
+    }
+
+    /// Index of the newline ending the line containing `index`, or the end of the text.
+    fn line_end(&self, index: usize) -> usize {
+        self.text[index..]
+            .iter()
+            .position(|c| *c == '\n')
+            .map_or(self.text.len(), |i| index + i)
+    }
+}
+
+/// Text typed by the key events of a session on the given layout.
+pub fn typed_text(session: &Session, layout: Layout) -> String {
+    let mut decoder = TextDecoder::new(layout);
+    for event in &session.events {
+        decoder.apply(&event.event);
+    }
+    decoder.text()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SessionBuilder;
+
+    #[test]
+    fn decode_typed_text() {
+        let text = "Grüße, à coûte 5€!\n\t~{x}";
+        let session = SessionBuilder::new()
+            .layout(Layout::De)
+            .type_text(text)
+            .press(Key::LCtrl)
+            .tap(Key::S)
+            .release(Key::LCtrl)
+            .build()
+            .unwrap();
+        assert_eq!(typed_text(&session, Layout::De), text);
+    }
+
+    #[test]
+    fn decode_edits() {
+        let session = SessionBuilder::new()
+            .type_text("helo\nword")
+            .tap(Key::Left)
+            .type_text("l")
+            .tap(Key::Up)
+            .tap(Key::Backspace)
+            .type_text("lo")
+            .tap(Key::End)
+            .type_text("!")
+            .tap(Key::Down)
+            .tap(Key::Capslock)
+            .type_text("ok!")
+            .tap(Key::Capslock)
+            .tap(Key::Home)
+            .tap(Key::Delete)
+            .build()
+            .unwrap();
+        assert_eq!(typed_text(&session, Layout::Us), "hello!\norldOK!");
+    }
+}